@@ -0,0 +1,159 @@
+//! Compare optimizers
+//! by objective value and wall time.
+//!
+//! Run with `cargo bench --bench optimizers`.
+//! Lower values are better.
+//! Each row is the mean over several seeds.
+//!
+//! `pbil-optimal` is PBIL from the `optimal` crate,
+//! the optimizer before the in-house PBIL.
+//! It cannot start from hints.
+//! Warm rows start other optimizers
+//! from the previous layout split,
+//! like the layout generator.
+
+use std::time::{Duration, Instant};
+
+use optimal::{optimizer::derivative_free::pbil::*, prelude::*};
+use owm::optimizer::{self, Control};
+use owm_problem::{
+    encoding::Decoder,
+    objective::Problem,
+    optimizer::{Algorithm, OptimizerConfig, SamplesPerCore},
+    templates::{main_stack, split_largest},
+    AreaRatio, AspectRatio, Rect, Size, Weight, Weights,
+};
+use rand::SeedableRng;
+use rand_xoshiro::SplitMix64;
+use rayon::prelude::*;
+
+const SEEDS: u64 = 3;
+
+fn main() {
+    let container = Size::new_checked(1920, 1080);
+    let max_size = Size::new_checked(1920, 1080);
+    println!("algorithm\tstart\tsamples_per_core\twindows\tseconds\tvalue");
+    for count in 2..=5 {
+        let decoder = Decoder::new(Size::new_checked(320, 180), max_size, container, count);
+        let problem = Problem::new(
+            Weights {
+                gaps_weight: Weight::new(5.0).unwrap(),
+                overlap_weight: Weight::new(6.0).unwrap(),
+                area_ratios_weight: Weight::new(1.5).unwrap(),
+                aspect_ratios_weight: Weight::new(3.0).unwrap(),
+                adjacent_close_weight: Weight::new(0.5).unwrap(),
+                reading_order_weight: Weight::new(0.5).unwrap(),
+                center_main_weight: Weight::new(1.5).unwrap(),
+                consistency_weight: Weight::new(1.0).unwrap(),
+            },
+            vec![
+                AreaRatio::new(3.0).unwrap(),
+                AreaRatio::new(2.0).unwrap(),
+                AreaRatio::new(1.0).unwrap(),
+            ],
+            vec![AspectRatio::new(1.77777).unwrap()],
+            max_size,
+            container,
+            main_stack(container, count - 1),
+        );
+        let prev_layout = main_stack(container, count - 1);
+        let hints = [split_largest(container, &prev_layout)];
+        // Fewer samples trade value for time.
+        for samples_per_core in [50, 125, 250, 500] {
+            let config = OptimizerConfig {
+                samples_per_core: SamplesPerCore::new(samples_per_core).unwrap(),
+                ..OptimizerConfig::default()
+            };
+            print_row("pbil-optimal", "cold", &config, count, &problem, |rng| {
+                optimal_pbil(&config, &decoder, &problem, rng)
+            });
+            for algorithm in Algorithm::ALL {
+                let config = OptimizerConfig {
+                    algorithm,
+                    ..config
+                };
+                let optimizer = optimizer::from_config(config);
+                for (start, hints) in [("cold", &[][..]), ("warm", &hints[..])] {
+                    print_row(
+                        &algorithm.to_string(),
+                        start,
+                        &config,
+                        count,
+                        &problem,
+                        |rng| {
+                            optimizer
+                                .optimize(&decoder, &problem, hints, rng, &Control::new(&|| false))
+                                .expect("optimizer should not be cancelled")
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Print the mean time and value
+/// of layouts from `optimize`
+/// over seeds.
+fn print_row(
+    algorithm: &str,
+    start: &str,
+    config: &OptimizerConfig,
+    count: usize,
+    problem: &Problem,
+    mut optimize: impl FnMut(&mut SplitMix64) -> Vec<Rect>,
+) {
+    let mut elapsed = Duration::ZERO;
+    let mut value = 0.0;
+    for seed in 0..SEEDS {
+        let start = Instant::now();
+        let rects = optimize(&mut SplitMix64::seed_from_u64(seed));
+        elapsed += start.elapsed();
+        value += problem.evaluate(&rects);
+    }
+    println!(
+        "{algorithm}\t{start}\t{}\t{count}\t{:.3}\t{:.4}",
+        config.samples_per_core,
+        elapsed.as_secs_f64() / SEEDS as f64,
+        value / SEEDS as f64
+    );
+}
+
+/// Return the layout found
+/// by PBIL from `optimal`
+/// with `config`.
+fn optimal_pbil(
+    config: &OptimizerConfig,
+    decoder: &Decoder,
+    problem: &Problem,
+    rng: &mut SplitMix64,
+) -> Vec<Rect> {
+    let cores = std::thread::available_parallelism().map_or(1, |x| x.into());
+    let point = UntilConvergedConfig {
+        threshold: ProbabilityThreshold::new(
+            Probability::new(config.converged_threshold.into_inner()).unwrap(),
+        )
+        .unwrap(),
+    }
+    .argmin(
+        &mut Config {
+            num_samples: NumSamples::new(config.num_samples(cores)).unwrap(),
+            adjust_rate: AdjustRate::new(config.adjust_rate.into_inner()).unwrap(),
+            mutation_chance: MutationChance::new(config.mutation_chance.into_inner()).unwrap(),
+            mutation_adjust_rate: MutationAdjustRate::new(config.mutation_adjust_rate.into_inner())
+                .unwrap(),
+        }
+        .start_using(
+            decoder.bits(),
+            |points| {
+                (0..points.nrows())
+                    .into_par_iter()
+                    .map(|i| problem.evaluate(decoder.decode1(points.row(i)).as_slice().unwrap()))
+                    .collect::<Vec<_>>()
+                    .into()
+            },
+            rng,
+        ),
+    );
+    decoder.decode1(point.view()).into_raw_vec()
+}
@@ -30,6 +30,7 @@ pub struct ToFracLE<T> {
     to_int: ToIntLE<T>,
     start: T,
     a: Option<T>,
+    bits_len: usize,
 }
 
 impl<T> ToFracLE<T> {
@@ -47,6 +48,7 @@ impl<T> ToFracLE<T> {
             },
             start,
             to_int,
+            bits_len,
         }
     }
 
@@ -61,6 +63,20 @@ impl<T> ToFracLE<T> {
     }
 }
 
+impl ToFracLE<f64> {
+    /// Return the bits decoding nearest to `x`,
+    /// clamped to range.
+    /// Leftmost is least significant.
+    pub fn encode(&self, x: f64) -> impl Iterator<Item = bool> {
+        let max = (1_u64 << self.bits_len) - 1;
+        let int = match self.a {
+            Some(a) => ((x - self.start) / a).round().clamp(0.0, max as f64) as u64,
+            None => 0,
+        };
+        (0..self.bits_len).map(move |i| int >> i & 1 == 1)
+    }
+}
+
 /// Reduce to base 10 integer representations of bits.
 /// Leftmost is least significant.
 ///
@@ -10,6 +10,7 @@ use crate::{
 
 #[derive(Clone, Debug)]
 pub struct Decoder {
+    min_size: Size,
     max_size: Size,
     container: Size,
     count: usize,
@@ -39,6 +40,7 @@ impl Decoder {
         let bits_per_width = reduced_bits_for(width_range.end() - width_range.start());
         let bits_per_height = reduced_bits_for(height_range.end() - height_range.start());
         Self {
+            min_size,
             max_size,
             container,
             count,
@@ -60,6 +62,18 @@ impl Decoder {
         }
     }
 
+    pub fn min_size(&self) -> Size {
+        self.min_size
+    }
+
+    pub fn max_size(&self) -> Size {
+        self.max_size
+    }
+
+    pub fn container(&self) -> Size {
+        self.container
+    }
+
     pub fn bits(&self) -> usize {
         self.bits_per_rect() * self.count
     }
@@ -68,6 +82,114 @@ impl Decoder {
         self.height_bits_range.end
     }
 
+    /// Return the bits decoding nearest to `rects`,
+    /// which must have one rect per window.
+    pub fn encode(&self, rects: &[Rect]) -> Array1<bool> {
+        debug_assert_eq!(rects.len(), self.count);
+        rects
+            .iter()
+            .flat_map(|rect| {
+                self.x_decoder
+                    .encode(rect.x() as f64)
+                    .chain(self.y_decoder.encode(rect.y() as f64))
+                    .chain(self.width_decoder.encode(rect.width().get() as f64))
+                    .chain(self.height_decoder.encode(rect.height().get() as f64))
+            })
+            .collect()
+    }
+
+    /// Return the reals decoding nearest to `rects`,
+    /// which must have one rect per window.
+    pub fn encode_reals(&self, rects: &[Rect]) -> Vec<f64> {
+        debug_assert_eq!(rects.len(), self.count);
+        let unscale = |x: usize, start: usize, end: usize| {
+            if end > start {
+                (x.saturating_sub(start) as f64 / (end - start) as f64).min(1.0)
+            } else {
+                0.0
+            }
+        };
+        let (x_max, y_max) = self.position_max();
+        rects
+            .iter()
+            .flat_map(|rect| {
+                [
+                    unscale(rect.x(), 0, x_max),
+                    unscale(rect.y(), 0, y_max),
+                    unscale(
+                        rect.width().get(),
+                        self.min_size.width.get(),
+                        self.max_size.width.get(),
+                    ),
+                    unscale(
+                        rect.height().get(),
+                        self.min_size.height.get(),
+                        self.max_size.height.get(),
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    /// Return the greatest x and y positions.
+    fn position_max(&self) -> (usize, usize) {
+        (
+            self.container
+                .width
+                .get()
+                .saturating_sub(self.min_size.width.get()),
+            self.container
+                .height
+                .get()
+                .saturating_sub(self.min_size.height.get()),
+        )
+    }
+
+    /// Return the number of reals
+    /// `decode_reals` takes.
+    pub fn reals(&self) -> usize {
+        4 * self.count
+    }
+
+    /// Decode reals from 0 to 1,
+    /// the x, y, width, and height of each window,
+    /// into rects.
+    ///
+    /// Unlike bits,
+    /// reals can represent every position and size.
+    /// Values outside 0 to 1 are clamped.
+    pub fn decode_reals(&self, xs: &[f64]) -> Vec<Rect> {
+        debug_assert_eq!(xs.len(), self.reals());
+        let scale = |x: f64, start: usize, end: usize| {
+            start + (x.clamp(0.0, 1.0) * (end - start) as f64).round() as usize
+        };
+        let (x_max, y_max) = self.position_max();
+        let mut rects = xs
+            .chunks_exact(4)
+            .map(|xs| {
+                Rect::new(
+                    scale(xs[0], 0, x_max),
+                    scale(xs[1], 0, y_max),
+                    NonZeroUsize::new(scale(
+                        xs[2],
+                        self.min_size.width.get(),
+                        self.max_size.width.get(),
+                    ))
+                    .expect("min width should be non-zero"),
+                    NonZeroUsize::new(scale(
+                        xs[3],
+                        self.min_size.height.get(),
+                        self.max_size.height.get(),
+                    ))
+                    .expect("min height should be non-zero"),
+                )
+            })
+            .collect::<Vec<_>>();
+        trim_outside(self.container, &mut rects);
+        remove_gaps(self.max_size, self.container, &mut rects);
+        rects
+    }
+
     pub fn decode1(&self, bits: ArrayView1<bool>) -> Array1<Rect> {
         Array::from_vec(
             self.decode2(bits.into_shape((1, bits.len())).unwrap())
@@ -126,3 +248,66 @@ fn bits_for(x: usize) -> usize {
         (x - 1).ilog2() as usize + 1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::{prop::collection::vec, *};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[test]
+    fn decode_reals_spans_ranges() {
+        let decoder = Decoder::new(
+            Size::new_checked(10, 10),
+            Size::new_checked(100, 50),
+            Size::new_checked(100, 50),
+            1,
+        );
+        assert_eq!(
+            decoder.decode_reals(&[0.0, 0.0, 1.0, 1.0]),
+            vec![Rect::new_checked(0, 0, 100, 50)]
+        );
+    }
+
+    #[test]
+    fn encode_inverts_decode() {
+        // Each bit moves a window 128 pixels.
+        let container = Size::new_checked(1024, 1024);
+        let decoder = Decoder::new(Size::new_checked(128, 128), container, container, 2);
+        let rects = vec![
+            Rect::new_checked(0, 0, 1024, 512),
+            Rect::new_checked(0, 512, 1024, 512),
+        ];
+        assert_eq!(
+            decoder.decode1(decoder.encode(&rects).view()).to_vec(),
+            rects
+        );
+    }
+
+    #[test]
+    fn encode_reals_inverts_decode_reals() {
+        let container = Size::new_checked(1920, 1080);
+        let decoder = Decoder::new(Size::new_checked(320, 180), container, container, 2);
+        let rects = vec![
+            Rect::new_checked(0, 0, 700, 1080),
+            Rect::new_checked(700, 0, 1220, 1080),
+        ];
+        assert_eq!(decoder.decode_reals(&decoder.encode_reals(&rects)), rects);
+    }
+
+    #[proptest]
+    fn decode_reals_returns_rects_in_container(
+        #[strategy(1_usize..=4)] count: usize,
+        #[strategy(vec(0.0..=1.0, 4 * #count))] xs: Vec<f64>,
+    ) {
+        let container = Size::new_checked(1920, 1080);
+        let decoder = Decoder::new(Size::new_checked(320, 180), container, container, count);
+        let rects = decoder.decode_reals(&xs);
+        prop_assert_eq!(rects.len(), count);
+        for rect in rects {
+            prop_assert!(rect.right() <= container.width.get());
+            prop_assert!(rect.bottom() <= container.height.get());
+        }
+    }
+}
@@ -4,12 +4,16 @@ mod rect;
 
 pub mod encoding;
 pub mod objective;
+pub mod optimizer;
 pub mod post_processing;
+pub mod render;
+pub mod templates;
 
 #[cfg(test)]
 pub mod testing;
 
 pub use crate::{
     objective::{AreaRatio, AspectRatio, Weight, Weights},
+    optimizer::OptimizerConfig,
     rect::{Pos, Rect, Size},
 };
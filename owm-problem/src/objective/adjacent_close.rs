@@ -2,6 +2,8 @@ use itertools::Itertools;
 
 use crate::{Pos, Rect, Size};
 
+use super::Objective;
+
 pub struct PlaceAdjacentClose {
     worst_case: f64,
 }
@@ -47,6 +49,16 @@ impl PlaceAdjacentClose {
     }
 }
 
+impl Objective for PlaceAdjacentClose {
+    fn name(&self) -> &str {
+        "adjacent_close"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
@@ -16,6 +16,8 @@ use crate::{
     Rect, Size,
 };
 
+use super::Objective;
+
 pub struct MaintainAreaRatios {
     ratios: Vec<AreaRatio>,
     worst_case: f64,
@@ -94,6 +96,16 @@ impl MaintainAreaRatios {
     }
 }
 
+impl Objective for MaintainAreaRatios {
+    fn name(&self) -> &str {
+        "area_ratios"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::{prop::collection::vec, *};
@@ -11,6 +11,8 @@ use crate::{
     Rect, Size,
 };
 
+use super::Objective;
+
 pub struct MaintainAspectRatios {
     ratios: Vec<AspectRatio>,
     worst_case: f64,
@@ -71,6 +73,16 @@ impl MaintainAspectRatios {
     }
 }
 
+impl Objective for MaintainAspectRatios {
+    fn name(&self) -> &str {
+        "aspect_ratios"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 fn abs_ratio(x: f64) -> f64 {
     if x < 1.0 {
         1.0 / x
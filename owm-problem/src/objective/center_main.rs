@@ -1,5 +1,7 @@
 use crate::{Pos, Rect, Size};
 
+use super::Objective;
+
 pub struct CenterMain {
     center: Pos,
     worst_case: f64,
@@ -24,6 +26,16 @@ impl CenterMain {
     }
 }
 
+impl Objective for CenterMain {
+    fn name(&self) -> &str {
+        "center_main"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::iter::once;
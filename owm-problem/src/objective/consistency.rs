@@ -1,5 +1,7 @@
 use crate::{Rect, Size};
 
+use super::Objective;
+
 pub struct MaximizeConsistency {
     previous_layout: Vec<Rect>,
     worst_case: f64,
@@ -51,6 +53,16 @@ impl MaximizeConsistency {
     }
 }
 
+impl Objective for MaximizeConsistency {
+    fn name(&self) -> &str {
+        "consistency"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
@@ -2,6 +2,8 @@ use std::num::NonZeroUsize;
 
 use crate::{rect::covered_area, Rect, Size};
 
+use super::Objective;
+
 pub struct MinimizeGaps {
     area: NonZeroUsize,
     worst_case: f64,
@@ -28,6 +30,16 @@ impl MinimizeGaps {
     }
 }
 
+impl Objective for MinimizeGaps {
+    fn name(&self) -> &str {
+        "gaps"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::iter::{once, repeat};
@@ -17,26 +17,62 @@ use crate::{
     rect::{Rect, Size},
 };
 
-use self::{
-    adjacent_close::PlaceAdjacentClose, area_ratios::MaintainAreaRatios,
-    aspect_ratios::MaintainAspectRatios, center_main::CenterMain, consistency::MaximizeConsistency,
-    gaps::MinimizeGaps, overlap::MinimizeOverlap, reading_order::PlaceInReadingOrder,
+pub use self::{
+    adjacent_close::PlaceAdjacentClose,
+    area_ratios::{AreaRatio, MaintainAreaRatios},
+    aspect_ratios::{AspectRatio, MaintainAspectRatios},
+    center_main::CenterMain,
+    consistency::MaximizeConsistency,
+    gaps::MinimizeGaps,
+    overlap::MinimizeOverlap,
+    reading_order::PlaceInReadingOrder,
 };
-pub use self::{area_ratios::AreaRatio, aspect_ratios::AspectRatio};
 
+/// A measure of how bad a layout is.
+///
+/// Objectives are typically built
+/// from the container,
+/// the number of windows,
+/// and the previous layout,
+/// like those in this module.
+pub trait Objective: Send + Sync {
+    /// Return a short name
+    /// identifying this objective
+    /// in diagnostics.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Return how bad `rects` is,
+    /// from 0 for best
+    /// to 1 for worst.
+    fn evaluate(&self, rects: &[Rect]) -> f64;
+}
+
+/// A weighted sum of objectives.
 pub struct Problem {
-    weights: Weights,
-    gaps: MinimizeGaps,
-    overlap: MinimizeOverlap,
-    area_ratios: MaintainAreaRatios,
-    aspect_ratios: MaintainAspectRatios,
-    adjacent_close: PlaceAdjacentClose,
-    reading_order: PlaceInReadingOrder,
-    center_main: CenterMain,
-    consistency: MaximizeConsistency,
+    objectives: Vec<(Weight, Box<dyn Objective>)>,
+}
+
+/// How one objective scored a layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectiveScore {
+    pub name: String,
+    pub weight: Weight,
+    /// Score from 0 for best
+    /// to 1 for worst,
+    /// before weighting.
+    pub value: f64,
+}
+
+impl ObjectiveScore {
+    /// Return the contribution to the weighted sum.
+    pub fn weighted(&self) -> f64 {
+        self.weight * self.value
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Weights {
     pub gaps_weight: Weight,
     pub overlap_weight: Weight,
@@ -70,6 +106,9 @@ impl Mul<f64> for Weight {
 }
 
 impl Problem {
+    /// Return the problem of laying out
+    /// one more window than `prev_layout`
+    /// using built-in objectives.
     pub fn new(
         weights: Weights,
         area_ratios: Vec<AreaRatio>,
@@ -79,52 +118,148 @@ impl Problem {
         prev_layout: Vec<Rect>,
     ) -> Self {
         let count = prev_layout.len() + 1;
+        Self::from_objectives([
+            (
+                weights.gaps_weight,
+                Box::new(MinimizeGaps::new(container)) as Box<dyn Objective>,
+            ),
+            (
+                weights.overlap_weight,
+                Box::new(MinimizeOverlap::new(container, count)),
+            ),
+            (
+                weights.area_ratios_weight,
+                Box::new(MaintainAreaRatios::new(area_ratios, max_size, count)),
+            ),
+            (
+                weights.aspect_ratios_weight,
+                Box::new(MaintainAspectRatios::new(aspect_ratios, max_size, count)),
+            ),
+            (
+                weights.adjacent_close_weight,
+                Box::new(PlaceAdjacentClose::new(container, count)),
+            ),
+            (
+                weights.reading_order_weight,
+                Box::new(PlaceInReadingOrder::new(count)),
+            ),
+            (
+                weights.center_main_weight,
+                Box::new(CenterMain::new(container)),
+            ),
+            (
+                weights.consistency_weight,
+                Box::new(MaximizeConsistency::new(container, prev_layout)),
+            ),
+        ])
+    }
+
+    /// Return the problem of minimizing
+    /// the weighted sum of `objectives`.
+    pub fn from_objectives(
+        objectives: impl IntoIterator<Item = (Weight, Box<dyn Objective>)>,
+    ) -> Self {
         Self {
-            weights,
-            gaps: MinimizeGaps::new(container),
-            overlap: MinimizeOverlap::new(container, count),
-            area_ratios: MaintainAreaRatios::new(area_ratios, max_size, count),
-            aspect_ratios: MaintainAspectRatios::new(aspect_ratios, max_size, count),
-            adjacent_close: PlaceAdjacentClose::new(container, count),
-            reading_order: PlaceInReadingOrder::new(count),
-            center_main: CenterMain::new(container),
-            consistency: MaximizeConsistency::new(container, prev_layout),
+            objectives: objectives.into_iter().collect(),
         }
     }
 
+    /// Add `objective` to the weighted sum.
+    pub fn push(&mut self, weight: Weight, objective: Box<dyn Objective>) {
+        self.objectives.push((weight, objective));
+    }
+
     pub fn evaluate(&self, rects: &[Rect]) -> f64 {
-        (if self.weights.gaps_weight > Weight(0.0) {
-            self.weights.gaps_weight * self.gaps.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.overlap_weight > Weight(0.0) {
-            self.weights.overlap_weight * self.overlap.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.area_ratios_weight > Weight(0.0) {
-            self.weights.area_ratios_weight * self.area_ratios.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.aspect_ratios_weight > Weight(0.0) {
-            self.weights.aspect_ratios_weight * self.aspect_ratios.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.adjacent_close_weight > Weight(0.0) {
-            self.weights.adjacent_close_weight * self.adjacent_close.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.reading_order_weight > Weight(0.0) {
-            self.weights.reading_order_weight * self.reading_order.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.center_main_weight > Weight(0.0) {
-            self.weights.center_main_weight * self.center_main.evaluate(rects)
-        } else {
-            0.0
-        }) + (if self.weights.consistency_weight > Weight(0.0) {
-            self.weights.consistency_weight * self.consistency.evaluate(rects)
-        } else {
-            0.0
-        })
+        self.objectives
+            .iter()
+            // Objectives without weight
+            // cannot affect the sum.
+            .filter(|(weight, _)| *weight > Weight(0.0))
+            .map(|(weight, objective)| *weight * objective.evaluate(rects))
+            .sum()
+    }
+
+    /// Return how each objective scores `rects`,
+    /// including those without weight.
+    /// Weighted scores sum to `evaluate`.
+    pub fn evaluate_detailed(&self, rects: &[Rect]) -> Vec<ObjectiveScore> {
+        self.objectives
+            .iter()
+            .map(|(weight, objective)| ObjectiveScore {
+                name: objective.name().to_owned(),
+                weight: *weight,
+                value: objective.evaluate(rects),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Constant(f64);
+
+    impl Objective for Constant {
+        fn evaluate(&self, _: &[Rect]) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn problem_sums_weighted_objectives() {
+        let problem = Problem::from_objectives([
+            (
+                Weight::new(2.0).unwrap(),
+                Box::new(Constant(0.25)) as Box<dyn Objective>,
+            ),
+            (Weight::new(0.5).unwrap(), Box::new(Constant(1.0))),
+            (Weight::new(0.0).unwrap(), Box::new(Constant(f64::NAN))),
+        ]);
+        assert_eq!(problem.evaluate(&[]), 1.0);
+    }
+
+    #[test]
+    fn push_adds_to_weighted_sum() {
+        let mut problem = Problem::from_objectives([(
+            Weight::new(1.0).unwrap(),
+            Box::new(Constant(0.25)) as Box<dyn Objective>,
+        )]);
+        problem.push(Weight::new(2.0).unwrap(), Box::new(Constant(0.5)));
+        assert_eq!(problem.evaluate(&[]), 1.25);
+        assert_eq!(problem.evaluate_detailed(&[]).len(), 2);
+    }
+
+    #[test]
+    fn evaluate_detailed_sums_to_evaluate() {
+        let container = Size::new_checked(1920, 1080);
+        let problem = Problem::new(
+            Weights {
+                gaps_weight: Weight::new(5.0).unwrap(),
+                overlap_weight: Weight::new(6.0).unwrap(),
+                area_ratios_weight: Weight::new(1.5).unwrap(),
+                aspect_ratios_weight: Weight::new(3.0).unwrap(),
+                adjacent_close_weight: Weight::new(0.5).unwrap(),
+                reading_order_weight: Weight::new(0.0).unwrap(),
+                center_main_weight: Weight::new(1.5).unwrap(),
+                consistency_weight: Weight::new(1.0).unwrap(),
+            },
+            vec![AreaRatio::new(2.0).unwrap()],
+            vec![AspectRatio::new(1.0).unwrap()],
+            container,
+            container,
+            vec![Rect::new_checked(0, 0, 1920, 1080)],
+        );
+        let rects = [
+            Rect::new_checked(0, 0, 1000, 1080),
+            Rect::new_checked(900, 100, 1020, 500),
+        ];
+        let scores = problem.evaluate_detailed(&rects);
+        assert_eq!(scores.len(), 8);
+        assert_eq!(scores[0].name, "gaps");
+        assert!(
+            (scores.iter().map(|x| x.weighted()).sum::<f64>() - problem.evaluate(&rects)).abs()
+                < 1e-12
+        );
     }
 }
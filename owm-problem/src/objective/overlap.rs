@@ -1,5 +1,7 @@
 use crate::{rect::obscured_area, Rect, Size};
 
+use super::Objective;
+
 pub struct MinimizeOverlap {
     worst_case: f64,
 }
@@ -19,6 +21,16 @@ impl MinimizeOverlap {
         }
     }
 }
+
+impl Objective for MinimizeOverlap {
+    fn name(&self) -> &str {
+        "overlap"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
 #[cfg(test)]
 mod tests {
     use std::iter::repeat;
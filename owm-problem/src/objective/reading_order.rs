@@ -2,6 +2,8 @@ use itertools::Itertools;
 
 use crate::Rect;
 
+use super::Objective;
+
 pub struct PlaceInReadingOrder {
     worst_case: f64,
 }
@@ -27,6 +29,16 @@ impl PlaceInReadingOrder {
     }
 }
 
+impl Objective for PlaceInReadingOrder {
+    fn name(&self) -> &str {
+        "reading_order"
+    }
+
+    fn evaluate(&self, rects: &[Rect]) -> f64 {
+        Self::evaluate(self, rects)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
@@ -0,0 +1,269 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use derive_more::Display;
+use num_traits::bounds::Bounded;
+
+use crate::derive::{
+    derive_from_str_from_try_into, derive_into_inner, derive_new_from_bounded_float,
+    derive_new_from_lower_bounded, derive_try_from_from_new,
+};
+
+/// Settings for the optimizer generating layouts.
+///
+/// Defaults favor quality over speed.
+/// Fewer samples,
+/// a higher adjust rate,
+/// or a lower threshold
+/// generate layouts faster,
+/// but likely worse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizerConfig {
+    /// Search used to generate layouts.
+    /// Other settings apply to all algorithms,
+    /// except where noted.
+    pub algorithm: Algorithm,
+    /// Number of layouts sampled each step,
+    /// per CPU core.
+    pub samples_per_core: SamplesPerCore,
+    /// How much each step moves toward the best sample.
+    /// Only for PBIL.
+    pub adjust_rate: AdjustRate,
+    /// Chance of randomly adjusting each bit every step.
+    /// Only for PBIL and the genetic algorithm.
+    pub mutation_chance: MutationChance,
+    /// How much random adjustments move.
+    /// Only for PBIL.
+    pub mutation_adjust_rate: MutationAdjustRate,
+    /// Optimization stops
+    /// when every bit is at least this likely
+    /// to be one value.
+    /// Only for PBIL and the genetic algorithm.
+    pub converged_threshold: ConvergedThreshold,
+    /// Time to generate each layout,
+    /// after which the best found so far is used,
+    /// if limited.
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::default(),
+            samples_per_core: SamplesPerCore(500),
+            adjust_rate: AdjustRate(0.1),
+            mutation_chance: MutationChance(0.0),
+            mutation_adjust_rate: MutationAdjustRate(0.05),
+            converged_threshold: ConvergedThreshold(0.9),
+            time_budget: None,
+        }
+    }
+}
+
+impl OptimizerConfig {
+    /// Return the number of layouts sampled each step
+    /// with `cores` CPU cores.
+    pub fn num_samples(&self, cores: usize) -> usize {
+        self.samples_per_core.0.saturating_mul(cores.max(1))
+    }
+}
+
+/// Search used to generate layouts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Population-based incremental learning.
+    #[default]
+    Pbil,
+    /// Genetic algorithm.
+    Genetic,
+    /// Simulated annealing,
+    /// searching positions and sizes directly
+    /// rather than bits.
+    Annealing,
+    /// Hill climbing
+    /// from random layouts.
+    LocalSearch,
+}
+
+/// Error returned when failing to parse an algorithm.
+#[derive(Clone, Debug, thiserror::Error, PartialEq)]
+#[error("unknown algorithm '{0}', expected one of: pbil, genetic, annealing, local-search")]
+pub struct ParseAlgorithmError(String);
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 4] = [
+        Algorithm::Pbil,
+        Algorithm::Genetic,
+        Algorithm::Annealing,
+        Algorithm::LocalSearch,
+    ];
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Pbil => "pbil",
+            Algorithm::Genetic => "genetic",
+            Algorithm::Annealing => "annealing",
+            Algorithm::LocalSearch => "local-search",
+        })
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Algorithm::ALL
+            .into_iter()
+            .find(|x| x.to_string() == s)
+            .ok_or_else(|| ParseAlgorithmError(s.to_owned()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, PartialOrd)]
+pub struct SamplesPerCore(usize);
+
+impl Bounded for SamplesPerCore {
+    fn min_value() -> Self {
+        // Each step compares samples.
+        Self(2)
+    }
+
+    fn max_value() -> Self {
+        Self(usize::MAX)
+    }
+}
+
+derive_new_from_lower_bounded!(SamplesPerCore(usize));
+derive_try_from_from_new!(SamplesPerCore(usize));
+derive_from_str_from_try_into!(SamplesPerCore(usize));
+derive_into_inner!(SamplesPerCore(usize));
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, PartialOrd)]
+pub struct AdjustRate(f64);
+
+impl Bounded for AdjustRate {
+    fn min_value() -> Self {
+        // Optimization never converges
+        // without adjusting.
+        Self(f64::EPSILON)
+    }
+
+    fn max_value() -> Self {
+        Self(1.0)
+    }
+}
+
+derive_new_from_bounded_float!(AdjustRate(f64));
+derive_try_from_from_new!(AdjustRate(f64));
+derive_from_str_from_try_into!(AdjustRate(f64));
+derive_into_inner!(AdjustRate(f64));
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, PartialOrd)]
+pub struct MutationChance(f64);
+
+impl Bounded for MutationChance {
+    fn min_value() -> Self {
+        Self(0.0)
+    }
+
+    fn max_value() -> Self {
+        Self(1.0)
+    }
+}
+
+derive_new_from_bounded_float!(MutationChance(f64));
+derive_try_from_from_new!(MutationChance(f64));
+derive_from_str_from_try_into!(MutationChance(f64));
+derive_into_inner!(MutationChance(f64));
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, PartialOrd)]
+pub struct MutationAdjustRate(f64);
+
+impl Bounded for MutationAdjustRate {
+    fn min_value() -> Self {
+        Self(f64::EPSILON)
+    }
+
+    fn max_value() -> Self {
+        Self(1.0)
+    }
+}
+
+derive_new_from_bounded_float!(MutationAdjustRate(f64));
+derive_try_from_from_new!(MutationAdjustRate(f64));
+derive_from_str_from_try_into!(MutationAdjustRate(f64));
+derive_into_inner!(MutationAdjustRate(f64));
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, PartialOrd)]
+pub struct ConvergedThreshold(f64);
+
+impl Bounded for ConvergedThreshold {
+    fn min_value() -> Self {
+        // Every probability is at least 0.5 likely
+        // to be one value or the other.
+        Self(0.5)
+    }
+
+    fn max_value() -> Self {
+        // Probabilities may never reach exactly 1.
+        Self(1.0 - f64::EPSILON)
+    }
+}
+
+derive_new_from_bounded_float!(ConvergedThreshold(f64));
+derive_try_from_from_new!(ConvergedThreshold(f64));
+derive_from_str_from_try_into!(ConvergedThreshold(f64));
+derive_into_inner!(ConvergedThreshold(f64));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_matches_previous_hyperparameters() {
+        let config = OptimizerConfig::default();
+        assert_eq!(config.algorithm, Algorithm::Pbil);
+        assert_eq!(config.num_samples(4), 2000);
+        assert_eq!(config.adjust_rate, AdjustRate::new(0.1).unwrap());
+        assert_eq!(config.mutation_chance, MutationChance::new(0.0).unwrap());
+        assert_eq!(
+            config.mutation_adjust_rate,
+            MutationAdjustRate::new(0.05).unwrap()
+        );
+        assert_eq!(
+            config.converged_threshold,
+            ConvergedThreshold::new(0.9).unwrap()
+        );
+    }
+
+    #[test]
+    fn algorithm_round_trips() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(algorithm.to_string().parse(), Ok(algorithm));
+        }
+        assert!("annealling".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn new_rejects_out_of_bounds_values() {
+        assert!(SamplesPerCore::new(1).is_err());
+        assert_eq!(
+            AdjustRate::new(0.0),
+            Err(InvalidAdjustRateError::TooLow(0.0))
+        );
+        assert_eq!(
+            MutationChance::new(1.5),
+            Err(InvalidMutationChanceError::TooHigh(1.5))
+        );
+        assert!(matches!(
+            MutationAdjustRate::new(f64::NAN),
+            Err(InvalidMutationAdjustRateError::IsNan(_))
+        ));
+        assert_eq!(
+            ConvergedThreshold::new(1.0),
+            Err(InvalidConvergedThresholdError::TooHigh(1.0))
+        );
+        assert!("0.4".parse::<ConvergedThreshold>().is_err());
+    }
+}
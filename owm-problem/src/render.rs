@@ -0,0 +1,251 @@
+use std::fmt::Write;
+
+use crate::rect::{Rect, Size};
+
+/// Character for container area
+/// no window covers.
+const EMPTY: char = '·';
+
+/// Character for window area
+/// more than one window covers.
+const OVERLAP: char = '▒';
+
+/// Return `rects` drawn as boxes
+/// on a grid `columns` characters wide,
+/// scaled from `container`,
+/// with one line per row.
+///
+/// Rows are scaled half as much as columns,
+/// because characters are about twice as tall as wide.
+/// Each window is labeled with its index.
+/// Uncovered area is dotted,
+/// and overlap is shaded.
+pub fn text(container: Size, rects: &[Rect], columns: usize) -> String {
+    let columns = columns.max(1);
+    let rows = ((columns * container.height.get()) as f64 / (2 * container.width.get()) as f64)
+        .round()
+        .max(1.0) as usize;
+    let cells = rects
+        .iter()
+        .map(|rect| {
+            (
+                cell_range(rect.left(), rect.right(), container.width.get(), columns),
+                cell_range(rect.top(), rect.bottom(), container.height.get(), rows),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut grid = vec![vec![EMPTY; columns]; rows];
+    for (row, line) in grid.iter_mut().enumerate() {
+        for (column, cell) in line.iter_mut().enumerate() {
+            match cells
+                .iter()
+                .filter(|(xs, ys)| xs.contains(&column) && ys.contains(&row))
+                .count()
+            {
+                0 => {}
+                1 => *cell = ' ',
+                _ => *cell = OVERLAP,
+            }
+        }
+    }
+    for (xs, ys) in &cells {
+        for row in ys.clone() {
+            for column in xs.clone() {
+                let (top, bottom) = (row == ys.start, row + 1 == ys.end);
+                let (left, right) = (column == xs.start, column + 1 == xs.end);
+                grid[row][column] = match (top, bottom, left, right) {
+                    (true, _, true, _) => '┌',
+                    (true, _, _, true) => '┐',
+                    (_, true, true, _) => '└',
+                    (_, true, _, true) => '┘',
+                    (true, _, _, _) | (_, true, _, _) => '─',
+                    (_, _, true, _) | (_, _, _, true) => '│',
+                    _ => continue,
+                };
+            }
+        }
+    }
+    for (i, (xs, ys)) in cells.iter().enumerate() {
+        // Labels go inside the border
+        // if there is room.
+        let row = if ys.len() > 2 { ys.start + 1 } else { ys.start };
+        let start = if xs.len() > 2 { xs.start + 1 } else { xs.start };
+        let end = if xs.len() > 2 { xs.end - 1 } else { xs.end };
+        for (column, c) in (start..end).zip(i.to_string().chars()) {
+            grid[row][column] = c;
+        }
+    }
+
+    grid.into_iter()
+        .map(|line| line.into_iter().chain(['\n']).collect::<String>())
+        .collect()
+}
+
+/// Return the range of `cells` equal cells
+/// spanning `container_len`
+/// that `start..end` falls in,
+/// at least one cell long.
+fn cell_range(
+    start: usize,
+    end: usize,
+    container_len: usize,
+    cells: usize,
+) -> std::ops::Range<usize> {
+    let scale = |x: usize| {
+        ((x * cells) as f64 / container_len as f64)
+            .round()
+            .min(cells as f64) as usize
+    };
+    let start = scale(start).min(cells - 1);
+    start..scale(end).max(start + 1)
+}
+
+/// Return `rects` as an SVG image
+/// the size of `container`.
+///
+/// The container is outlined,
+/// each window is labeled with its index,
+/// and overlap is highlighted in red.
+pub fn svg(container: Size, rects: &[Rect]) -> String {
+    let (width, height) = (container.width, container.height);
+    let font_size = (width.get().min(height.get()) / 20).max(1);
+    let mut s = String::new();
+    writeln!(
+        s,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )
+    .unwrap();
+    writeln!(
+        s,
+        r##"  <rect x="0" y="0" width="{width}" height="{height}" fill="#eeeeee" stroke="#000000" stroke-width="2"/>"##
+    )
+    .unwrap();
+    for rect in rects {
+        writeln!(
+            s,
+            r##"  <rect x="{}" y="{}" width="{}" height="{}" fill="#8fb3d9" fill-opacity="0.5" stroke="#1f3f5f" stroke-width="2"/>"##,
+            rect.x(),
+            rect.y(),
+            rect.width(),
+            rect.height()
+        )
+        .unwrap();
+    }
+    for (i, rect) in rects.iter().enumerate() {
+        for other in &rects[i + 1..] {
+            if let Some(overlap) = rect.overlap(other) {
+                writeln!(
+                    s,
+                    r##"  <rect x="{}" y="{}" width="{}" height="{}" fill="#ff0000" fill-opacity="0.5"/>"##,
+                    overlap.x(),
+                    overlap.y(),
+                    overlap.width(),
+                    overlap.height()
+                )
+                .unwrap();
+            }
+        }
+    }
+    for (i, rect) in rects.iter().enumerate() {
+        writeln!(
+            s,
+            r#"  <text x="{}" y="{}" font-size="{font_size}" text-anchor="middle" dominant-baseline="middle">{i}</text>"#,
+            rect.center_x(),
+            rect.center_y()
+        )
+        .unwrap();
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_draws_labeled_boxes() {
+        let container = Size::new_checked(100, 100);
+        let rects = [
+            Rect::new_checked(0, 0, 50, 100),
+            Rect::new_checked(50, 0, 50, 50),
+            Rect::new_checked(50, 50, 50, 50),
+        ];
+        assert_eq!(
+            text(container, &rects, 20),
+            concat!(
+                "┌────────┐┌────────┐\n",
+                "│0       ││1       │\n",
+                "│        ││        │\n",
+                "│        ││        │\n",
+                "│        │└────────┘\n",
+                "│        │┌────────┐\n",
+                "│        ││2       │\n",
+                "│        ││        │\n",
+                "│        ││        │\n",
+                "└────────┘└────────┘\n",
+            )
+        );
+    }
+
+    #[test]
+    fn text_dots_gaps_and_shades_overlap() {
+        let container = Size::new_checked(100, 100);
+        let rects = [
+            Rect::new_checked(0, 0, 60, 60),
+            Rect::new_checked(30, 20, 60, 60),
+        ];
+        assert_eq!(
+            text(container, &rects, 20),
+            concat!(
+                "┌──────────┐········\n",
+                "│0         │········\n",
+                "│     ┌──────────┐··\n",
+                "│     │1▒▒▒│     │··\n",
+                "│     │▒▒▒▒│     │··\n",
+                "└─────│────┘     │··\n",
+                "······│          │··\n",
+                "······└──────────┘··\n",
+                "····················\n",
+                "····················\n",
+            )
+        );
+    }
+
+    #[test]
+    fn text_draws_windows_smaller_than_a_cell() {
+        let container = Size::new_checked(1000, 1000);
+        let rects = [Rect::new_checked(0, 0, 1, 1)];
+        assert_eq!(text(container, &rects, 4), "0···\n····\n");
+    }
+
+    #[test]
+    fn svg_outlines_container_and_highlights_overlap() {
+        let container = Size::new_checked(200, 100);
+        let rects = [
+            Rect::new_checked(0, 0, 120, 100),
+            Rect::new_checked(100, 0, 100, 100),
+        ];
+        assert_eq!(
+            svg(container, &rects),
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 200 100">"##,
+                "\n",
+                r##"  <rect x="0" y="0" width="200" height="100" fill="#eeeeee" stroke="#000000" stroke-width="2"/>"##,
+                "\n",
+                r##"  <rect x="0" y="0" width="120" height="100" fill="#8fb3d9" fill-opacity="0.5" stroke="#1f3f5f" stroke-width="2"/>"##,
+                "\n",
+                r##"  <rect x="100" y="0" width="100" height="100" fill="#8fb3d9" fill-opacity="0.5" stroke="#1f3f5f" stroke-width="2"/>"##,
+                "\n",
+                r##"  <rect x="100" y="0" width="20" height="100" fill="#ff0000" fill-opacity="0.5"/>"##,
+                "\n",
+                r##"  <text x="60" y="50" font-size="5" text-anchor="middle" dominant-baseline="middle">0</text>"##,
+                "\n",
+                r##"  <text x="150" y="50" font-size="5" text-anchor="middle" dominant-baseline="middle">1</text>"##,
+                "\n",
+                "</svg>\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::num::NonZeroUsize;
+
+use crate::rect::{Rect, Size};
+
+/// Return every classic layout
+/// for `count` windows in `container`.
+pub fn all(container: Size, count: usize) -> Vec<Vec<Rect>> {
+    vec![
+        main_stack(container, count),
+        grid(container, count),
+        spiral(container, count),
+        centered_main(container, count),
+    ]
+}
+
+/// Return a layout with the first window on the left
+/// and the rest stacked on the right.
+///
+/// Windows tile the container
+/// if it is at least `count` pixels tall.
+pub fn main_stack(container: Size, count: usize) -> Vec<Rect> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![Rect::new(0, 0, container.width, container.height)],
+        _ => {
+            let main_width = container.width.get() / 2;
+            let stack = Size::new(
+                NonZeroUsize::new(container.width.get() - main_width).unwrap(),
+                container.height,
+            );
+            std::iter::once(Rect::new(
+                0,
+                0,
+                NonZeroUsize::new(main_width).unwrap_or(stack.width),
+                container.height,
+            ))
+            .chain(split(stack.height, count - 1).map(|(y, height)| {
+                Rect::new(
+                    container.width.get() - stack.width.get(),
+                    y,
+                    stack.width,
+                    height,
+                )
+            }))
+            .collect()
+        }
+    }
+}
+
+/// Return a layout with windows in rows of equal height,
+/// as close to square as possible,
+/// filled left to right,
+/// top to bottom.
+/// The last row may have fewer, wider windows.
+///
+/// Windows tile the container
+/// if it is at least `count` pixels wide and tall.
+pub fn grid(container: Size, count: usize) -> Vec<Rect> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let columns = (1..=count).find(|x| x * x >= count).unwrap();
+    let rows = count.div_ceil(columns);
+    split(container.height, rows)
+        .enumerate()
+        .flat_map(|(row, (y, height))| {
+            let row_count = columns.min(count - row * columns);
+            split(container.width, row_count).map(move |(x, width)| Rect::new(x, y, width, height))
+        })
+        .collect()
+}
+
+/// Return a layout with each window
+/// taking half the remaining space,
+/// turning clockwise
+/// from the left.
+/// The last window takes all remaining space.
+///
+/// Windows tile the container
+/// if it is at least `2^(count / 2)` pixels wide and tall.
+pub fn spiral(container: Size, count: usize) -> Vec<Rect> {
+    let mut rest = Rect::new(0, 0, container.width, container.height);
+    let mut rects = Vec::with_capacity(count);
+    for i in 0..count {
+        if i + 1 == count {
+            rects.push(rest);
+            break;
+        }
+        let (first, second) = halve(rest, i % 2 == 0);
+        // Left, top, right, bottom.
+        let (rect, remaining) = if i % 4 < 2 {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        rects.push(rect);
+        rest = remaining;
+    }
+    rects
+}
+
+/// Return a layout with the first window
+/// in the middle half of the container,
+/// and the rest stacked on the right,
+/// then left.
+/// Two or fewer windows use `main_stack`.
+///
+/// Windows tile the container
+/// if it is at least 4 pixels wide
+/// and `count` pixels tall.
+pub fn centered_main(container: Size, count: usize) -> Vec<Rect> {
+    if count <= 2 || container.width.get() < 4 {
+        return main_stack(container, count);
+    }
+    let width = container.width.get();
+    let left_width = width / 4;
+    let main_width = width / 2;
+    let right_width = width - left_width - main_width;
+    let left_count = (count - 1) / 2;
+    let right_count = count - 1 - left_count;
+    let stack = |x: usize, width: usize, count: usize| {
+        let width = NonZeroUsize::new(width).unwrap();
+        split(container.height, count).map(move |(y, height)| Rect::new(x, y, width, height))
+    };
+    std::iter::once(Rect::new(
+        left_width,
+        0,
+        NonZeroUsize::new(main_width).unwrap(),
+        container.height,
+    ))
+    .chain(stack(left_width + main_width, right_width, right_count))
+    .chain(stack(0, left_width, left_count))
+    .collect()
+}
+
+/// Split `rect` in half,
+/// side by side if `vertical`,
+/// or one above the other.
+fn halve(rect: Rect, vertical: bool) -> (Rect, Rect) {
+    let mut parts = if vertical {
+        split(rect.width(), 2)
+            .map(|(x, width)| Rect::new(rect.x() + x, rect.y(), width, rect.height()))
+            .collect::<Vec<_>>()
+    } else {
+        split(rect.height(), 2)
+            .map(|(y, height)| Rect::new(rect.x(), rect.y() + y, rect.width(), height))
+            .collect()
+    };
+    let second = parts.pop().unwrap();
+    (parts.pop().unwrap(), second)
+}
+
+/// Return `layout` with one more window,
+/// splitting the largest window in half
+/// along its longer side,
+/// the new window taking the second half.
+///
+/// With no windows,
+/// the new window fills the container.
+pub fn split_largest(container: Size, layout: &[Rect]) -> Vec<Rect> {
+    let mut rects = layout.to_vec();
+    // Later windows are less important,
+    // so ties split the last.
+    match rects
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(_, x)| x.area())
+    {
+        Some((i, rect)) => {
+            let (first, second) = halve(rect, rect.width() >= rect.height());
+            rects[i] = first;
+            rects.push(second);
+        }
+        None => rects.push(Rect::new(0, 0, container.width, container.height)),
+    }
+    rects
+}
+
+/// Split `len` into `count` nearly equal parts,
+/// returning the start and length of each.
+///
+/// Parts are at least one long,
+/// and may overlap if `len` is less than `count`.
+fn split(len: NonZeroUsize, count: usize) -> impl Iterator<Item = (usize, NonZeroUsize)> {
+    let len = len.get();
+    (0..count).map(move |i| {
+        let start = (i * len / count).min(len - 1);
+        let end = (i + 1) * len / count;
+        (
+            start,
+            NonZeroUsize::new(end - start).unwrap_or(NonZeroUsize::MIN),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use crate::{
+        rect::{covered_area, obscured_area},
+        render::text,
+    };
+
+    use super::*;
+
+    #[test]
+    fn main_stack_splits_container() {
+        assert_eq!(
+            main_stack(Size::new_checked(10, 9), 4),
+            [
+                Rect::new_checked(0, 0, 5, 9),
+                Rect::new_checked(5, 0, 5, 3),
+                Rect::new_checked(5, 3, 5, 3),
+                Rect::new_checked(5, 6, 5, 3),
+            ]
+        )
+    }
+
+    #[test]
+    fn grid_fills_rows_left_to_right() {
+        assert_eq!(
+            grid(Size::new_checked(10, 9), 5),
+            [
+                Rect::new_checked(0, 0, 3, 4),
+                Rect::new_checked(3, 0, 3, 4),
+                Rect::new_checked(6, 0, 4, 4),
+                Rect::new_checked(0, 4, 5, 5),
+                Rect::new_checked(5, 4, 5, 5),
+            ]
+        )
+    }
+
+    #[test]
+    fn spiral_turns_clockwise() {
+        assert_eq!(
+            spiral(Size::new_checked(8, 8), 4),
+            [
+                Rect::new_checked(0, 0, 4, 8),
+                Rect::new_checked(4, 0, 4, 4),
+                Rect::new_checked(6, 4, 2, 4),
+                Rect::new_checked(4, 4, 2, 4),
+            ]
+        )
+    }
+
+    #[test]
+    fn centered_main_stacks_right_then_left() {
+        assert_eq!(
+            centered_main(Size::new_checked(8, 4), 4),
+            [
+                Rect::new_checked(2, 0, 4, 4),
+                Rect::new_checked(6, 0, 2, 2),
+                Rect::new_checked(6, 2, 2, 2),
+                Rect::new_checked(0, 0, 2, 4),
+            ]
+        )
+    }
+
+    #[test]
+    fn templates_snapshot() {
+        let container = Size::new_checked(1920, 1080);
+        assert_eq!(
+            all(container, 5)
+                .iter()
+                .map(|layout| text(container, layout, 48))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            concat!(
+                "┌──────────────────────┐┌──────────────────────┐\n",
+                "│0                     ││1                     │\n",
+                "│                      ││                      │\n",
+                "│                      │└──────────────────────┘\n",
+                "│                      │┌──────────────────────┐\n",
+                "│                      ││2                     │\n",
+                "│                      │└──────────────────────┘\n",
+                "│                      │┌──────────────────────┐\n",
+                "│                      ││3                     │\n",
+                "│                      ││                      │\n",
+                "│                      │└──────────────────────┘\n",
+                "│                      │┌──────────────────────┐\n",
+                "│                      ││4                     │\n",
+                "└──────────────────────┘└──────────────────────┘\n",
+                "\n",
+                "┌──────────────┐┌──────────────┐┌──────────────┐\n",
+                "│0             ││1             ││2             │\n",
+                "│              ││              ││              │\n",
+                "│              ││              ││              │\n",
+                "│              ││              ││              │\n",
+                "│              ││              ││              │\n",
+                "└──────────────┘└──────────────┘└──────────────┘\n",
+                "┌──────────────────────┐┌──────────────────────┐\n",
+                "│3                     ││4                     │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "└──────────────────────┘└──────────────────────┘\n",
+                "\n",
+                "┌──────────────────────┐┌──────────────────────┐\n",
+                "│0                     ││1                     │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "│                      ││                      │\n",
+                "│                      │└──────────────────────┘\n",
+                "│                      │┌──────────┐┌──────────┐\n",
+                "│                      ││4         ││2         │\n",
+                "│                      ││          ││          │\n",
+                "│                      │└──────────┘│          │\n",
+                "│                      │┌──────────┐│          │\n",
+                "│                      ││3         ││          │\n",
+                "└──────────────────────┘└──────────┘└──────────┘\n",
+                "\n",
+                "┌──────────┐┌──────────────────────┐┌──────────┐\n",
+                "│3         ││0                     ││1         │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "└──────────┘│                      │└──────────┘\n",
+                "┌──────────┐│                      │┌──────────┐\n",
+                "│4         ││                      ││2         │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "│          ││                      ││          │\n",
+                "└──────────┘└──────────────────────┘└──────────┘\n",
+            )
+        )
+    }
+
+    #[proptest]
+    fn templates_return_count_rects(container: Size, #[strategy(0_usize..=16)] count: usize) {
+        for rects in all(container, count) {
+            prop_assert_eq!(rects.len(), count);
+        }
+    }
+
+    #[proptest]
+    fn templates_tile_container(container: Size, #[strategy(1_usize..=16)] count: usize) {
+        prop_assume!(container.width.get() >= 256 && container.height.get() >= 256);
+        for rects in all(container, count) {
+            prop_assert_eq!(covered_area(&rects), container.area().get());
+            prop_assert_eq!(obscured_area(&rects), 0);
+        }
+    }
+
+    #[test]
+    fn split_largest_halves_largest_window() {
+        assert_eq!(
+            split_largest(
+                Size::new_checked(10, 9),
+                &[Rect::new_checked(0, 0, 4, 9), Rect::new_checked(4, 0, 6, 9)]
+            ),
+            [
+                Rect::new_checked(0, 0, 4, 9),
+                Rect::new_checked(4, 0, 6, 4),
+                Rect::new_checked(4, 4, 6, 5),
+            ]
+        )
+    }
+
+    #[proptest]
+    fn split_largest_keeps_tiling(container: Size, #[strategy(0_usize..=16)] count: usize) {
+        prop_assume!(container.width.get() >= 2 && container.height.get() >= count);
+        let rects = split_largest(container, &main_stack(container, count));
+        prop_assert_eq!(rects.len(), count + 1);
+        prop_assert_eq!(
+            covered_area(&rects),
+            covered_area(&main_stack(container, count.max(1)))
+        );
+    }
+
+    #[proptest]
+    fn main_stack_returns_count_rects(container: Size, #[strategy(0_usize..=16)] count: usize) {
+        prop_assert_eq!(main_stack(container, count).len(), count);
+    }
+
+    #[proptest]
+    fn main_stack_tiles_container(container: Size, #[strategy(1_usize..=16)] count: usize) {
+        prop_assume!(container.width.get() >= 2 && container.height.get() >= count);
+        let rects = main_stack(container, count);
+        prop_assert_eq!(covered_area(&rects), container.area().get());
+        prop_assert_eq!(obscured_area(&rects), 0);
+    }
+}
@@ -0,0 +1,1771 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, io, thread};
+
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser};
+use owm::{Command, ConfigFile, LayoutConfig, LayoutGen, Setting, Status};
+use owm_problem::{
+    objective::ObjectiveScore,
+    optimizer::{
+        AdjustRate, Algorithm, ConvergedThreshold, MutationAdjustRate, MutationChance,
+        SamplesPerCore,
+    },
+    render, AreaRatio, AspectRatio, OptimizerConfig, Rect, Size, Weight, Weights,
+};
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+    Dispatch, Proxy,
+};
+use wayland_client::{ConnectError, Connection, DispatchError, EventQueue, QueueHandle};
+
+use crate::protocol::{
+    river_layout_manager_v3::RiverLayoutManagerV3,
+    river_layout_v3::{self, RiverLayoutV3},
+    zriver_command_callback_v1::ZriverCommandCallbackV1,
+    zriver_control_v1::ZriverControlV1,
+};
+
+#[derive(Clone, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Configuration file.
+    ///
+    /// Defaults to `$XDG_CONFIG_HOME/owm/config.toml`.
+    /// Options given on the command line
+    /// take precedence over the configuration file.
+    /// Changes to the file are applied
+    /// while running,
+    /// except for `namespace`,
+    /// `cache_file`,
+    /// and `no_cache_file`.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// File to save generated layouts in,
+    /// so they are kept after restarting.
+    ///
+    /// Defaults to `$XDG_CACHE_HOME/owm/layouts`.
+    #[arg(long, value_name = "PATH")]
+    cache_file: Option<PathBuf>,
+
+    /// Do not save generated layouts to a file.
+    #[arg(long, conflicts_with = "cache_file")]
+    no_cache_file: bool,
+
+    /// Maximum number of layouts to keep in memory.
+    ///
+    /// Least recently used layouts are discarded first.
+    /// An empty value means no limit.
+    #[arg(long, value_name = "NON_ZERO_UINT", value_parser = non_zero_usize_option_parser, default_value = "1000")]
+    max_cached_layouts: std::option::Option<NonZeroUsize>,
+
+    /// Generate layouts for up to this many windows
+    /// before they are needed,
+    /// at low priority.
+    ///
+    /// Layouts for each output are generated
+    /// after its first layout demand,
+    /// and the layout for one more window
+    /// after every layout demand.
+    /// 0 disables generating layouts early.
+    #[arg(long, value_name = "UINT", default_value = "4")]
+    precompute_up_to: usize,
+
+    /// River namespace for this instance of the layout generator.
+    /// Multiple instances can run simultaneously
+    /// using different namespaces.
+    /// Instances can be switched between
+    /// using `riverctl default-layout NAMESPACE`
+    /// or `riverctl output-layout NAMESPACE`.
+    #[arg(long, value_name = "NAMESPACE", default_value = "owm")]
+    namespace: String,
+
+    /// Try `NAMESPACE-2`,
+    /// `NAMESPACE-3`,
+    /// and so on,
+    /// if the namespace is in use.
+    #[arg(long)]
+    numbered_namespace: bool,
+
+    /// Seconds to keep trying to reconnect
+    /// after losing the connection to River.
+    ///
+    /// Generated layouts are kept
+    /// across reconnects.
+    /// 0 disables reconnecting.
+    #[arg(long, value_name = "SECONDS", default_value = "0")]
+    reconnect_timeout: u64,
+
+    #[arg(long, value_name = "NON_ZERO_UINT", default_value_t = NonZeroUsize::new(320).unwrap())]
+    min_width: NonZeroUsize,
+
+    #[arg(long, value_name = "NON_ZERO_UINT", default_value_t = NonZeroUsize::new(180).unwrap())]
+    min_height: NonZeroUsize,
+
+    #[arg(long, value_name = "NON_ZERO_UINT", value_parser = non_zero_usize_option_parser, default_value = "1920")]
+    max_width: std::option::Option<NonZeroUsize>,
+
+    #[arg(long, value_name = "NON_ZERO_UINT", value_parser = non_zero_usize_option_parser, default_value = "")]
+    max_height: std::option::Option<NonZeroUsize>,
+
+    /// Set to border thickness
+    /// to fully overlap borders.
+    #[arg(long, value_name = "UINT", default_value = "0")]
+    overlap_borders_by: usize,
+
+    /// Importance of "minimize gaps" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(5.0).unwrap())]
+    gaps_weight: Weight,
+
+    /// Importance of "minimize overlap" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(6.0).unwrap())]
+    overlap_weight: Weight,
+
+    /// Desired area ratios between each window and the next.
+    ///
+    /// Values are comma-separated.
+    /// Last value is repeated for further pairs.
+    /// Each value must be >= 1.
+    #[arg(
+        long,
+        value_name = "RATIOS",
+        value_delimiter = ',',
+        default_value = "3,2,1"
+    )]
+    area_ratios: Vec<AreaRatio>,
+
+    /// Importance of "maintain area ratios" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(1.5).unwrap())]
+    area_ratios_weight: Weight,
+
+    /// Desired aspect ratios of windows.
+    ///
+    /// Values are comma-separated.
+    /// Last value is repeated for further windows.
+    /// Each value must be > 0.
+    #[arg(
+        long,
+        value_name = "RATIOS",
+        value_delimiter = ',',
+        default_value = "1.77777"
+    )]
+    aspect_ratios: Vec<AspectRatio>,
+
+    /// Importance of "maintain aspect ratios" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(3.0).unwrap())]
+    aspect_ratios_weight: Weight,
+
+    /// Importance of "place adjacent close" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(0.5).unwrap())]
+    adjacent_close_weight: Weight,
+
+    /// Importance of "place in reading order" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(0.5).unwrap())]
+    reading_order_weight: Weight,
+
+    /// Importance of "center main" objective.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(1.5).unwrap())]
+    center_main_weight: Weight,
+
+    /// Importance of keeping layout consistent
+    /// from one number of windows
+    /// to the next.
+    #[arg(long, value_name = "WEIGHT", default_value_t = Weight::new(1.0).unwrap())]
+    consistency_weight: Weight,
+
+    /// Seed for generating layouts.
+    ///
+    /// Different seeds generate different layouts
+    /// for the same settings.
+    #[arg(long, value_name = "UINT", default_value = "0")]
+    seed: u64,
+
+    /// Number of alternative layouts
+    /// to generate for each number of windows.
+    ///
+    /// Use `next-variant` and `prev-variant` commands
+    /// to switch between them.
+    /// Each variant takes as long to generate
+    /// as a single layout.
+    #[arg(long, value_name = "NON_ZERO_UINT", default_value_t = NonZeroUsize::new(1).unwrap())]
+    variants: NonZeroUsize,
+
+    /// Search used to generate layouts:
+    /// `pbil`,
+    /// `genetic`,
+    /// `annealing`,
+    /// or `local-search`.
+    #[arg(long, value_name = "ALGORITHM", default_value_t = Algorithm::Pbil)]
+    algorithm: Algorithm,
+
+    /// Number of layouts sampled
+    /// each optimization step,
+    /// per CPU core.
+    ///
+    /// Fewer samples generate layouts faster,
+    /// but likely worse.
+    #[arg(long, value_name = "UINT", default_value_t = SamplesPerCore::new(500).unwrap())]
+    samples_per_core: SamplesPerCore,
+
+    /// How much each optimization step
+    /// moves toward the best sample.
+    /// Only for `pbil`.
+    ///
+    /// Higher rates generate layouts faster,
+    /// but likely worse.
+    #[arg(long, value_name = "RATE", default_value_t = AdjustRate::new(0.1).unwrap())]
+    adjust_rate: AdjustRate,
+
+    /// Chance of randomly adjusting each bit
+    /// every optimization step.
+    /// Only for `pbil` and `genetic`.
+    #[arg(long, value_name = "PROBABILITY", default_value_t = MutationChance::new(0.0).unwrap())]
+    mutation_chance: MutationChance,
+
+    /// How much random adjustments move.
+    /// Only for `pbil`.
+    #[arg(long, value_name = "RATE", default_value_t = MutationAdjustRate::new(0.05).unwrap())]
+    mutation_adjust_rate: MutationAdjustRate,
+
+    /// Optimization stops
+    /// when every bit is at least this likely
+    /// to be one value.
+    ///
+    /// Lower thresholds generate layouts faster,
+    /// but likely worse.
+    /// Must be from 0.5 to less than 1.
+    /// Only for `pbil` and `genetic`.
+    #[arg(long, value_name = "PROBABILITY", default_value_t = ConvergedThreshold::new(0.9).unwrap())]
+    converged_threshold: ConvergedThreshold,
+
+    /// Milliseconds to generate each layout,
+    /// after which the best found so far is used.
+    ///
+    /// With multiple variants,
+    /// the time is split between them.
+    /// An empty value means no limit.
+    #[arg(long, value_name = "MILLISECONDS", value_parser = u64_option_parser, default_value = "")]
+    time_budget: std::option::Option<u64>,
+
+    /// Show improving layouts
+    /// while they are generated,
+    /// instead of a fallback layout.
+    #[arg(long)]
+    anytime: bool,
+
+    /// Print how each objective scores
+    /// every layout sent to River.
+    #[arg(long)]
+    debug: bool,
+
+    /// Setting for a specific output.
+    ///
+    /// `OUTPUT` is the output name,
+    /// like `DP-1`.
+    /// `NAME` is an option above
+    /// with `_` in place of `-`.
+    /// May be given multiple times.
+    #[arg(long, value_name = "OUTPUT NAME VALUE", value_parser = output_setting_parser)]
+    output_setting: Vec<(String, Setting)>,
+
+    /// Setting for a specific tag.
+    ///
+    /// `TAG` is the tag number,
+    /// starting from 1.
+    /// `NAME` is an option above
+    /// with `_` in place of `-`.
+    /// Windows are laid out
+    /// using settings of the lowest visible tag
+    /// with settings of its own.
+    /// Tag settings take precedence over output settings.
+    /// May be given multiple times.
+    #[arg(long, value_name = "TAG NAME VALUE", value_parser = tag_setting_parser)]
+    tag_setting: Vec<(u32, Setting)>,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+/// Run instead of the layout generator.
+#[derive(Clone, clap::Subcommand)]
+enum Action {
+    /// Generate the layout
+    /// for `COUNT` windows
+    /// in a `WIDTHxHEIGHT` container,
+    /// and print how each objective scores it.
+    ///
+    /// Layout generator options
+    /// must come before `explain`.
+    Explain(LayoutArgs),
+    /// Generate the layout
+    /// for `COUNT` windows
+    /// in a `WIDTHxHEIGHT` container,
+    /// and print it.
+    ///
+    /// Layout generator options
+    /// must come before `generate`.
+    Generate(GenerateArgs),
+}
+
+/// Layout to explain or generate.
+#[derive(Clone, clap::Args)]
+struct LayoutArgs {
+    /// Size of the area windows are laid out in.
+    #[arg(value_name = "WIDTHxHEIGHT", value_parser = size_parser)]
+    container: Size,
+
+    /// Number of windows.
+    #[arg(value_name = "COUNT")]
+    count: usize,
+
+    /// Use settings for this output.
+    #[arg(long, value_name = "OUTPUT", default_value = "")]
+    output: String,
+
+    /// Use settings for this tag,
+    /// starting from 1.
+    #[arg(long, value_name = "TAG", value_parser = tag_parser)]
+    tag: Option<u32>,
+}
+
+#[derive(Clone, clap::Args)]
+struct GenerateArgs {
+    #[command(flatten)]
+    layout: LayoutArgs,
+
+    /// Print layouts for 1 to `COUNT` windows
+    /// as an array.
+    #[arg(long)]
+    chain: bool,
+
+    /// Format to print layouts in.
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Width of text drawings
+    /// in characters.
+    #[arg(long, value_name = "COLUMNS", default_value_t = 80)]
+    columns: usize,
+}
+
+/// Format to print layouts in.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// Array of windows,
+    /// each an object
+    /// with `x`, `y`, `width`, and `height`.
+    Json,
+    /// Boxes drawn with characters.
+    Text,
+    /// SVG image.
+    /// Incompatible with `--chain`.
+    Svg,
+}
+
+impl LayoutArgs {
+    /// Return tags to use settings of.
+    fn tags(&self) -> u32 {
+        // `tag_parser` returns a bitfield.
+        self.tag.unwrap_or(0)
+    }
+}
+
+fn size_parser(s: &str) -> Result<Size, String> {
+    let (width, height) = s.split_once('x').ok_or("expected 'WIDTHxHEIGHT'")?;
+    Ok(Size::new(
+        width.parse().map_err(|e| format!("invalid width: {e}"))?,
+        height.parse().map_err(|e| format!("invalid height: {e}"))?,
+    ))
+}
+
+fn tag_parser(s: &str) -> Result<u32, String> {
+    owm::parse_tag(s).map_err(|e| e.to_string())
+}
+
+fn output_setting_parser(s: &str) -> Result<(String, Setting), String> {
+    let (output, setting) = s
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("expected 'OUTPUT NAME VALUE'")?;
+    Ok((
+        output.to_owned(),
+        setting
+            .parse()
+            .map_err(|e: owm::ParseSettingError| e.to_string())?,
+    ))
+}
+
+fn tag_setting_parser(s: &str) -> Result<(u32, Setting), String> {
+    let (tag, setting) = s
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("expected 'TAG NAME VALUE'")?;
+    Ok((
+        owm::parse_tag(tag).map_err(|e| e.to_string())?,
+        setting
+            .parse()
+            .map_err(|e: owm::ParseSettingError| e.to_string())?,
+    ))
+}
+
+fn non_zero_usize_option_parser(
+    s: &str,
+) -> Result<Option<NonZeroUsize>, <NonZeroUsize as FromStr>::Err> {
+    option_parser(s)
+}
+
+fn u64_option_parser(s: &str) -> Result<Option<u64>, <u64 as FromStr>::Err> {
+    option_parser(s)
+}
+
+fn option_parser<T>(s: &str) -> Result<Option<T>, <T as FromStr>::Err>
+where
+    T: FromStr,
+{
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        s.parse().map(Some)
+    }
+}
+
+/// Time between attempts to reconnect.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Error that stops the layout generator.
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    /// Options or configuration file are invalid.
+    #[error("{0}")]
+    Config(String),
+    /// Compositor could not be reached.
+    #[error("failed to connect to compositor: {0}")]
+    Connect(#[from] ConnectError),
+    /// Connection to compositor failed.
+    #[error("lost connection to compositor: {0}")]
+    Dispatch(#[from] DispatchError),
+    /// Compositor does not support a required protocol.
+    #[error("compositor does not support `{0}`")]
+    MissingGlobal(&'static str),
+    /// Another layout generator uses the namespace.
+    #[error("namespace '{0}' in use: layout program may already be running")]
+    NamespaceInUse(String),
+    /// Layout was cancelled
+    /// or failed to generate.
+    #[error("failed to generate layout for {0} windows")]
+    Generate(usize),
+}
+
+impl Error {
+    /// Return whether reconnecting may resolve this error.
+    fn is_disconnect(&self) -> bool {
+        matches!(self, Error::Connect(_) | Error::Dispatch(_))
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options = Options::parse();
+    let config = options.read_config().map_err(Error::Config)?;
+    let mut gen = LayoutGen::with_config(options.layout_config(&config).map_err(Error::Config)?);
+    let run_options = options.run_options(&config);
+    run_options.apply(&mut gen);
+    if let Some(action) = options.args.action.clone() {
+        return run_action(action, gen);
+    }
+    if let Some(path) = &run_options.cache_path {
+        if let Err(e) = gen.use_cache_file(path) {
+            eprintln!(
+                "warning: failed to open cache file '{}': {e}",
+                path.display()
+            );
+        }
+    }
+    let namespace = options.namespace(&config);
+    let config_path = options.config_path.clone();
+
+    let mut layout_manager = LayoutManager::new(namespace, gen, options, run_options);
+    if let Some(path) = config_path {
+        watch_config(
+            path,
+            layout_manager.sender.clone(),
+            Arc::clone(&layout_manager.reload_pending),
+        );
+    }
+    match layout_manager.run(|| Ok(Connection::connect_to_env()?)) {
+        Ok(never) => match never {},
+        Err(e) => Err(e),
+    }
+}
+
+/// Options from the command line
+/// and configuration file.
+#[derive(Clone)]
+struct Options {
+    args: Args,
+    /// Ids of arguments given on the command line.
+    explicit: HashSet<String>,
+    config_path: Option<PathBuf>,
+}
+
+/// Options that are not layout settings,
+/// from the command line
+/// or configuration file.
+#[derive(Clone, Debug, PartialEq)]
+struct RunOptions {
+    max_cached_layouts: Option<NonZeroUsize>,
+    precompute_up_to: usize,
+    anytime: bool,
+    cache_path: Option<PathBuf>,
+    reconnect_timeout: Duration,
+    numbered_namespace: bool,
+    debug: bool,
+}
+
+impl Options {
+    fn parse() -> Self {
+        Self::parse_from(std::env::args_os())
+    }
+
+    fn parse_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Args::command().get_matches_from(args);
+        let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let explicit = matches
+            .ids()
+            .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+            .map(|id| id.to_string())
+            .collect();
+        let config_path = args.config.clone().or_else(|| {
+            xdg_dir("XDG_CONFIG_HOME", ".config").map(|x| x.join("owm").join("config.toml"))
+        });
+        Self {
+            args,
+            explicit,
+            config_path,
+        }
+    }
+
+    fn read_config(&self) -> Result<ConfigFile, String> {
+        match &self.config_path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(s) => s
+                    .parse()
+                    .map_err(|e| format!("invalid configuration file '{}': {e}", path.display())),
+                // Only an explicitly given configuration file must exist.
+                Err(e) if e.kind() == io::ErrorKind::NotFound && self.args.config.is_none() => {
+                    Ok(ConfigFile::default())
+                }
+                Err(e) => Err(format!(
+                    "failed to read configuration file '{}': {e}",
+                    path.display()
+                )),
+            },
+            None => Ok(ConfigFile::default()),
+        }
+    }
+
+    fn namespace(&self, config: &ConfigFile) -> String {
+        self.choose(
+            "namespace",
+            self.args.namespace.clone(),
+            config.namespace.clone(),
+        )
+    }
+
+    fn run_options(&self, config: &ConfigFile) -> RunOptions {
+        let args = &self.args;
+        // A cache file given on the command line
+        // takes precedence over `no_cache_file` in the configuration file.
+        let no_cache_file = !self.explicit.contains("cache_file")
+            && self.choose("no_cache_file", args.no_cache_file, config.no_cache_file);
+        let cache_path = if no_cache_file {
+            None
+        } else {
+            self.choose(
+                "cache_file",
+                args.cache_file.clone(),
+                config.cache_file.clone().map(Some),
+            )
+            .or_else(|| xdg_dir("XDG_CACHE_HOME", ".cache").map(|x| x.join("owm").join("layouts")))
+        };
+        RunOptions {
+            max_cached_layouts: self.choose(
+                "max_cached_layouts",
+                args.max_cached_layouts,
+                config.max_cached_layouts,
+            ),
+            precompute_up_to: self.choose(
+                "precompute_up_to",
+                args.precompute_up_to,
+                config.precompute_up_to,
+            ),
+            anytime: self.choose("anytime", args.anytime, config.anytime),
+            cache_path,
+            reconnect_timeout: Duration::from_secs(self.choose(
+                "reconnect_timeout",
+                args.reconnect_timeout,
+                config.reconnect_timeout,
+            )),
+            numbered_namespace: self.choose(
+                "numbered_namespace",
+                args.numbered_namespace,
+                config.numbered_namespace,
+            ),
+            debug: self.choose("debug", args.debug, config.debug),
+        }
+    }
+
+    /// Return `config`,
+    /// unless the argument `id` was given on the command line,
+    /// in which case return `arg`.
+    fn choose<T>(&self, id: &str, arg: T, config: Option<T>) -> T {
+        match config {
+            Some(x) if !self.explicit.contains(id) => x,
+            _ => arg,
+        }
+    }
+
+    fn layout_config(&self, config: &ConfigFile) -> Result<LayoutConfig, String> {
+        let args = self.args.clone();
+        let mut layout_config = LayoutConfig::new(
+            args.min_width,
+            args.min_height,
+            args.max_width,
+            args.max_height,
+            args.overlap_borders_by,
+            Weights {
+                gaps_weight: args.gaps_weight,
+                overlap_weight: args.overlap_weight,
+                area_ratios_weight: args.area_ratios_weight,
+                aspect_ratios_weight: args.aspect_ratios_weight,
+                adjacent_close_weight: args.adjacent_close_weight,
+                reading_order_weight: args.reading_order_weight,
+                center_main_weight: args.center_main_weight,
+                consistency_weight: args.consistency_weight,
+            },
+            args.area_ratios,
+            args.aspect_ratios,
+            args.seed,
+            args.variants,
+            OptimizerConfig {
+                algorithm: args.algorithm,
+                samples_per_core: args.samples_per_core,
+                adjust_rate: args.adjust_rate,
+                mutation_chance: args.mutation_chance,
+                mutation_adjust_rate: args.mutation_adjust_rate,
+                converged_threshold: args.converged_threshold,
+                time_budget: args.time_budget.map(Duration::from_millis),
+            },
+        );
+        layout_config
+            .set(
+                config
+                    .settings
+                    .iter()
+                    .filter(|x| !self.explicit.contains(x.name()))
+                    .cloned(),
+            )
+            .map_err(|e| format!("invalid settings: {e}"))?;
+        for (output, settings) in &config.outputs {
+            layout_config
+                .set_for_output(output, settings.iter().cloned())
+                .map_err(|e| format!("invalid settings for output '{output}': {e}"))?;
+        }
+        for (tags, settings) in &config.tags {
+            layout_config
+                .set_for_tags(*tags, settings.iter().cloned())
+                .map_err(|e| format!("invalid settings for tag {}: {e}", tags.ilog2() + 1))?;
+        }
+        for (output, setting) in args.output_setting {
+            layout_config
+                .set_for_output(&output, [setting])
+                .map_err(|e| {
+                    format!("invalid value for '--output-setting <OUTPUT NAME VALUE>': {e}")
+                })?;
+        }
+        for (tags, setting) in args.tag_setting {
+            layout_config
+                .set_for_tags(tags, [setting])
+                .map_err(|e| format!("invalid value for '--tag-setting <TAG NAME VALUE>': {e}"))?;
+        }
+        Ok(layout_config)
+    }
+}
+
+impl RunOptions {
+    /// Use options of `gen`
+    /// from these options.
+    fn apply(&self, gen: &mut LayoutGen) {
+        gen.set_max_cached_layouts(self.max_cached_layouts);
+        gen.set_max_precomputed_count(self.precompute_up_to);
+        gen.set_anytime(self.anytime);
+    }
+}
+
+/// Return the XDG base directory in `var`,
+/// falling back to `default` in the home directory.
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|x| PathBuf::from(x).join(default)))
+}
+
+/// Reload the configuration file
+/// whenever it changes.
+///
+/// Commands sent through River may be lost,
+/// such as while reconnecting,
+/// so `reload-config` is sent every second
+/// until the layout manager clears `pending`.
+fn watch_config(path: PathBuf, sender: CommandSender, pending: Arc<AtomicBool>) {
+    let modified = move || -> Option<SystemTime> { fs::metadata(&path).ok()?.modified().ok() };
+    let mut last_modified = modified();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        let current = modified();
+        if current != last_modified {
+            last_modified = current;
+            pending.store(true, Ordering::Relaxed);
+        }
+        if pending.load(Ordering::Relaxed) {
+            sender.send("reload-config");
+        }
+    });
+}
+
+/// Sends layout commands to this layout generator
+/// through River,
+/// so other threads can wake the event loop.
+/// Clones share the current connection.
+#[derive(Clone, Default)]
+struct CommandSender(Arc<Mutex<Option<RiverControl>>>);
+
+struct RiverControl {
+    namespace: String,
+    control: ZriverControlV1,
+    seat: WlSeat,
+    qhandle: QueueHandle<LayoutManager>,
+    conn: Connection,
+}
+
+impl CommandSender {
+    /// Send future commands
+    /// through a new connection.
+    fn connect(&self, river: RiverControl) {
+        *self.0.lock().unwrap() = Some(river);
+    }
+
+    /// Send a command,
+    /// if connected.
+    fn send(&self, command: &str) {
+        if let Some(river) = self.0.lock().unwrap().as_ref() {
+            river.control.add_argument("send-layout-cmd".to_owned());
+            river.control.add_argument(river.namespace.clone());
+            river.control.add_argument(command.to_owned());
+            river.control.run_command(&river.seat, &river.qhandle, ());
+            let _ = river.conn.flush();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct OutputId(ObjectId);
+
+impl OutputId {
+    pub fn new(output: &WlOutput) -> OutputId {
+        OutputId(output.id())
+    }
+}
+
+/// An output
+/// and the layout object for it.
+struct Output {
+    /// Name of the `wl_output` global in the registry.
+    global_name: u32,
+    output: WlOutput,
+    /// Name of the output,
+    /// like `DP-1`.
+    /// This will be initialized
+    /// by a Wayland event.
+    name: Option<String>,
+    layout: Option<RiverLayoutV3>,
+    /// Whether layouts were generated
+    /// before being demanded
+    /// for this output.
+    warmed: bool,
+}
+
+impl Output {
+    /// Destroy objects for this output.
+    fn destroy(self) {
+        if let Some(layout) = self.layout {
+            layout.destroy();
+        }
+        // `release` was added in version 3.
+        if self.output.version() >= 3 {
+            self.output.release();
+        }
+    }
+}
+
+pub struct LayoutManager {
+    namespace: String,
+    gen: LayoutGen,
+    options: Options,
+    run_options: RunOptions,
+    // These will be initialized
+    // by Wayland events.
+    seat: Option<WlSeat>,
+    manager: Option<RiverLayoutManagerV3>,
+    control: Option<ZriverControlV1>,
+    outputs: HashMap<OutputId, Output>,
+    // River sends focused tags
+    // directly before each user command.
+    command_tags: Option<u32>,
+    sender: CommandSender,
+    /// Whether the configuration file changed
+    /// since it was last applied.
+    reload_pending: Arc<AtomicBool>,
+    // Event handlers cannot return errors,
+    // so they leave them here.
+    error: Option<Error>,
+}
+
+impl LayoutManager {
+    fn new(
+        namespace: String,
+        mut gen: LayoutGen,
+        options: Options,
+        run_options: RunOptions,
+    ) -> Self {
+        let sender = CommandSender::default();
+        let finished_sender = sender.clone();
+        gen.on_finish(move || {
+            // River will send a new layout demand
+            // if it receives a layout command.
+            finished_sender.send("retry-layout");
+        });
+        Self {
+            namespace,
+            gen,
+            options,
+            run_options,
+            seat: None,
+            manager: None,
+            control: None,
+            outputs: HashMap::new(),
+            command_tags: None,
+            sender,
+            reload_pending: Arc::default(),
+            error: None,
+        }
+    }
+
+    /// Apply the configuration file again.
+    fn reload_config(&mut self) {
+        self.reload_pending.store(false, Ordering::Relaxed);
+        match self.options.read_config().and_then(|config| {
+            Ok((
+                self.options.layout_config(&config)?,
+                self.options.run_options(&config),
+            ))
+        }) {
+            Ok((layout_config, run_options)) => {
+                self.gen.set_from(&layout_config);
+                run_options.apply(&mut self.gen);
+                self.run_options = run_options;
+            }
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+}
+
+impl LayoutManager {
+    /// Bind globals
+    /// through `conn`,
+    /// keeping generated layouts.
+    fn connect_to(&mut self, conn: Connection) -> Result<EventQueue<Self>, Error> {
+        self.seat = None;
+        self.manager = None;
+        self.control = None;
+        // Objects from the previous connection are already gone.
+        self.outputs.clear();
+        self.command_tags = None;
+        self.error = None;
+
+        let mut event_queue = conn.new_event_queue();
+        // `get_registry` has necessary side-effects.
+        let _registry = conn.display().get_registry(&event_queue.handle(), ());
+        event_queue.roundtrip(self)?;
+
+        let seat = self.seat.clone().ok_or(Error::MissingGlobal("wl_seat"))?;
+        if self.manager.is_none() {
+            return Err(Error::MissingGlobal("river_layout_manager_v3"));
+        }
+        let control = self
+            .control
+            .clone()
+            .ok_or(Error::MissingGlobal("zriver_control_v1"))?;
+        self.sender.connect(RiverControl {
+            namespace: self.namespace.clone(),
+            control,
+            seat,
+            qhandle: event_queue.handle(),
+            conn,
+        });
+        Ok(event_queue)
+    }
+
+    /// Handle events
+    /// from connections returned by `connect`,
+    /// reconnecting after losing the connection,
+    /// until an error occurs
+    /// that reconnecting cannot resolve.
+    fn run(
+        &mut self,
+        mut connect: impl FnMut() -> Result<Connection, Error>,
+    ) -> Result<Infallible, Error> {
+        let namespace = self.namespace.clone();
+        let mut namespace_number = 1;
+        let mut disconnected_at = None;
+        loop {
+            let result =
+                connect()
+                    .and_then(|conn| self.connect_to(conn))
+                    .and_then(|mut event_queue| {
+                        disconnected_at = None;
+                        self.dispatch(&mut event_queue)
+                    });
+            match result {
+                Ok(never) => match never {},
+                Err(e @ Error::NamespaceInUse(_)) if self.run_options.numbered_namespace => {
+                    namespace_number += 1;
+                    self.namespace = format!("{namespace}-{namespace_number}");
+                    eprintln!("warning: {e}, trying '{}'", self.namespace);
+                }
+                Err(e) if e.is_disconnect() => {
+                    if disconnected_at.get_or_insert_with(Instant::now).elapsed()
+                        >= self.run_options.reconnect_timeout
+                    {
+                        return Err(e);
+                    }
+                    eprintln!("warning: {e}, reconnecting");
+                    thread::sleep(RECONNECT_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Handle events
+    /// until an error occurs.
+    fn dispatch(&mut self, event_queue: &mut EventQueue<Self>) -> Result<Infallible, Error> {
+        loop {
+            event_queue.blocking_dispatch(self)?;
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for LayoutManager {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: <WlRegistry as Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        qhandle: &wayland_client::QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => match interface.as_str() {
+                "wl_seat" => {
+                    state.seat = Some(registry.bind::<WlSeat, _, Self>(name, version, qhandle, ()));
+                }
+                "wl_output" => {
+                    let output = registry.bind::<WlOutput, _, Self>(name, version, qhandle, ());
+                    state.outputs.insert(
+                        OutputId::new(&output),
+                        Output {
+                            global_name: name,
+                            output,
+                            name: None,
+                            layout: None,
+                            warmed: false,
+                        },
+                    );
+                }
+                "river_layout_manager_v3" => {
+                    state.manager = Some(registry.bind::<RiverLayoutManagerV3, _, Self>(
+                        name,
+                        version,
+                        qhandle,
+                        (),
+                    ));
+                }
+                "zriver_control_v1" => {
+                    state.control =
+                        Some(registry.bind::<ZriverControlV1, _, Self>(name, version, qhandle, ()));
+                }
+                _ => {}
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                // Other globals are not expected to be removed
+                // while River is running.
+                if let Some(id) = state
+                    .outputs
+                    .iter()
+                    .find(|(_, output)| output.global_name == name)
+                    .map(|(id, _)| id.clone())
+                {
+                    let output = state.outputs.remove(&id).unwrap();
+                    if let Some(name) = &output.name {
+                        state.gen.remove_output(name);
+                    }
+                    output.destroy();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for LayoutManager {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        _: &wayland_client::QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlOutput, ()> for LayoutManager {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: <WlOutput as Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        qhandle: &wayland_client::QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            let id = OutputId::new(output);
+            let Some(output) = state.outputs.get_mut(&id) else {
+                return;
+            };
+            output.name = Some(name);
+            if output.layout.is_none() {
+                output.layout = Some(
+                    state
+                        .manager
+                        .as_ref()
+                        .expect("layout manager should be bound before outputs")
+                        .get_layout(&output.output, state.namespace.clone(), qhandle, id),
+                );
+            }
+        }
+    }
+}
+
+impl Dispatch<RiverLayoutV3, OutputId> for LayoutManager {
+    fn event(
+        state: &mut Self,
+        proxy: &RiverLayoutV3,
+        event: <RiverLayoutV3 as wayland_client::Proxy>::Event,
+        output: &OutputId,
+        _: &wayland_client::Connection,
+        _: &wayland_client::QueueHandle<Self>,
+    ) {
+        // Events may arrive
+        // after the output is removed.
+        let Some(output_name) = state.outputs.get(output).map(|x| {
+            x.name
+                .clone()
+                .expect("output name should be known before requesting layouts")
+        }) else {
+            return;
+        };
+        match event {
+            river_layout_v3::Event::LayoutDemand {
+                view_count,
+                usable_width,
+                usable_height,
+                tags,
+                serial,
+            } => {
+                let container = Size::new(
+                    NonZeroUsize::new(usable_width as usize).expect("width should be non-zero"),
+                    NonZeroUsize::new(usable_height as usize).expect("height should be non-zero"),
+                );
+                let view_count = view_count as usize;
+
+                match state
+                    .gen
+                    .try_layout(&output_name, tags, container, view_count)
+                {
+                    Status::Finished(layout) => {
+                        push_layout(proxy, layout, serial);
+                        if state.run_options.debug {
+                            if let Some(scores) =
+                                state.gen.explain(&output_name, tags, container, view_count)
+                            {
+                                eprintln!(
+                                    "debug: {output_name} {}x{} {view_count} windows: {}",
+                                    container.width,
+                                    container.height,
+                                    format_scores(&scores)
+                                );
+                            }
+                        }
+                    }
+                    // A better layout will replace this
+                    // when found.
+                    Status::Intermediate(layout) => {
+                        push_layout(proxy, &layout, serial);
+                        state.gen.layout(&output_name, tags, container, view_count);
+                    }
+                    // The generated layout will replace this
+                    // when it finishes.
+                    // It may have been started speculatively,
+                    // with nothing waiting for it.
+                    Status::NotStarted | Status::Started => {
+                        push_layout(
+                            proxy,
+                            &state
+                                .gen
+                                .fallback_layout(&output_name, tags, container, view_count),
+                            serial,
+                        );
+                        state.gen.layout(&output_name, tags, container, view_count);
+                    }
+                }
+
+                let warmed = &mut state
+                    .outputs
+                    .get_mut(output)
+                    .expect("output should exist")
+                    .warmed;
+                if !*warmed {
+                    *warmed = true;
+                    state.gen.warm(&output_name, tags, container);
+                }
+                state
+                    .gen
+                    .precompute(&output_name, tags, container, view_count);
+            }
+            river_layout_v3::Event::UserCommandTags { tags } => {
+                state.command_tags = Some(tags);
+            }
+            river_layout_v3::Event::UserCommand { command } => {
+                let tags = state.command_tags.take();
+                match command.parse() {
+                    // River will send a new layout demand
+                    // after this event.
+                    Ok(Command::RetryLayout) => {}
+                    Ok(Command::Set(setting)) => {
+                        if let Err(e) = state.gen.set([setting]) {
+                            eprintln!("error: invalid setting in command '{command}': {e}");
+                        }
+                    }
+                    Ok(Command::SetForOutput(setting)) => {
+                        if let Err(e) = state.gen.set_for_output(&output_name, [setting]) {
+                            eprintln!("error: invalid setting in command '{command}': {e}");
+                        }
+                    }
+                    Ok(Command::ResetForOutput) => state.gen.reset_output(&output_name),
+                    Ok(Command::SetForTags(setting)) => match tags {
+                        Some(tags) => {
+                            if let Err(e) = state.gen.set_for_tags(tags, [setting]) {
+                                eprintln!("error: invalid setting in command '{command}': {e}");
+                            }
+                        }
+                        None => eprintln!("error: River did not send tags for command '{command}'"),
+                    },
+                    Ok(Command::ResetForTags) => match tags {
+                        Some(tags) => state.gen.reset_tags(tags),
+                        None => eprintln!("error: River did not send tags for command '{command}'"),
+                    },
+                    Ok(Command::ReloadConfig) => state.reload_config(),
+                    Ok(Command::Reroll) => state.gen.reroll(&output_name),
+                    Ok(Command::NextVariant) => state.gen.next_variant(&output_name),
+                    Ok(Command::PrevVariant) => state.gen.prev_variant(&output_name),
+                    Err(e) => eprintln!("error: invalid command '{command}': {e}"),
+                }
+            }
+            river_layout_v3::Event::NamespaceInUse => {
+                state.error = Some(Error::NamespaceInUse(state.namespace.clone()));
+            }
+        }
+    }
+}
+
+fn run_action(action: Action, mut gen: LayoutGen) -> Result<(), Error> {
+    match action {
+        Action::Explain(args) => {
+            let tags = args.tags();
+            gen.generate(&args.output, tags, args.container, args.count)
+                .ok_or(Error::Generate(args.count))?;
+            match gen.explain(&args.output, tags, args.container, args.count) {
+                Some(scores) => print_scores(&scores),
+                None => println!("no windows to explain"),
+            }
+        }
+        Action::Generate(GenerateArgs {
+            layout: args,
+            chain,
+            format,
+            columns,
+        }) => {
+            if chain && format == Format::Svg {
+                return Err(Error::Config(
+                    "`--chain` cannot be used with `--format svg`".to_owned(),
+                ));
+            }
+            let tags = args.tags();
+            let counts = if chain {
+                1..=args.count
+            } else {
+                args.count..=args.count
+            };
+            let layouts = counts
+                .map(|count| {
+                    gen.generate(&args.output, tags, args.container, count)
+                        .ok_or(Error::Generate(count))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match format {
+                Format::Json if chain => {
+                    println!("[");
+                    for (i, layout) in layouts.iter().enumerate() {
+                        let separator = if i + 1 < layouts.len() { "," } else { "" };
+                        println!("{}{separator}", format_layout_json(layout, "  "));
+                    }
+                    println!("]");
+                }
+                Format::Json => println!("{}", format_layout_json(&layouts[0], "")),
+                Format::Text => println!(
+                    "{}",
+                    layouts
+                        .iter()
+                        .map(|layout| render::text(args.container, layout, columns))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                        .trim_end()
+                ),
+                Format::Svg => print!("{}", render::svg(args.container, &layouts[0])),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return `layout` as a JSON array,
+/// one window per line,
+/// with each line starting with `indent`.
+fn format_layout_json(layout: &[Rect], indent: &str) -> String {
+    if layout.is_empty() {
+        return format!("{indent}[]");
+    }
+    let windows = layout
+        .iter()
+        .map(|rect| {
+            format!(
+                "{indent}  {{\"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}}}",
+                rect.x(),
+                rect.y(),
+                rect.width(),
+                rect.height()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!("{indent}[\n{windows}\n{indent}]")
+}
+
+/// Print scores as a table,
+/// with their total.
+fn print_scores(scores: &[ObjectiveScore]) {
+    let total = scores.iter().map(|x| x.weighted()).sum::<f64>();
+    println!(
+        "{:<16}{:>8}{:>10}{:>10}",
+        "objective", "weight", "value", "weighted"
+    );
+    for score in scores {
+        println!(
+            "{:<16}{:>8}{:>10.4}{:>10.4}",
+            score.name,
+            score.weight.to_string(),
+            score.value,
+            score.weighted()
+        );
+    }
+    println!("{:<16}{:>8}{:>10}{:>10.4}", "total", "", "", total);
+}
+
+/// Return scores on one line,
+/// largest contributions first.
+fn format_scores(scores: &[ObjectiveScore]) -> String {
+    let mut scores = scores.iter().collect::<Vec<_>>();
+    scores.sort_by(|x, y| y.weighted().total_cmp(&x.weighted()));
+    format!(
+        "total {:.4}: {}",
+        scores.iter().map(|x| x.weighted()).sum::<f64>(),
+        scores
+            .iter()
+            .map(|x| format!("{} {:.4}", x.name, x.weighted()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn push_layout(proxy: &RiverLayoutV3, layout: &[Rect], serial: u32) {
+    for rect in layout {
+        proxy.push_view_dimensions(
+            rect.x() as i32,
+            rect.y() as i32,
+            rect.width().get() as u32,
+            rect.height().get() as u32,
+            serial,
+        );
+    }
+    proxy.commit("owm".to_owned(), serial);
+}
+
+impl Dispatch<RiverLayoutManagerV3, ()> for LayoutManager {
+    fn event(
+        _: &mut Self,
+        _: &RiverLayoutManagerV3,
+        _: <RiverLayoutManagerV3 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        _: &wayland_client::QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZriverControlV1, ()> for LayoutManager {
+    fn event(
+        _: &mut Self,
+        _: &ZriverControlV1,
+        _: <ZriverControlV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        _: &wayland_client::QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZriverCommandCallbackV1, ()> for LayoutManager {
+    fn event(
+        _: &mut Self,
+        _: &ZriverCommandCallbackV1,
+        _: <ZriverCommandCallbackV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        _: &wayland_client::QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod mock_river;
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_river::MockRiver;
+
+    use super::*;
+
+    const CONTAINER: (u32, u32) = (1920, 1080);
+
+    /// Return a layout manager
+    /// connected to `river`,
+    /// generating layouts quickly.
+    fn connect(river: &MockRiver) -> (LayoutManager, EventQueue<LayoutManager>) {
+        connect_with(river, options(&[]))
+    }
+
+    /// Return options
+    /// for generating layouts quickly,
+    /// followed by `args`.
+    fn options(args: &[&str]) -> Options {
+        let mut options = Options::parse_from(
+            [
+                "owm",
+                "--no-cache-file",
+                "--samples-per-core",
+                "10",
+                "--precompute-up-to",
+                "0",
+            ]
+            .iter()
+            .chain(args),
+        );
+        options.config_path = None;
+        options
+    }
+
+    /// Return a layout manager
+    /// with `options`,
+    /// without a configuration file.
+    fn manager_with(options: Options) -> LayoutManager {
+        let config = ConfigFile::default();
+        let gen = LayoutGen::with_config(options.layout_config(&config).unwrap());
+        let run_options = options.run_options(&config);
+        LayoutManager::new("owm".to_owned(), gen, options, run_options)
+    }
+
+    /// Return a layout manager
+    /// with `options`
+    /// connected to `river`.
+    fn connect_with(
+        river: &MockRiver,
+        options: Options,
+    ) -> (LayoutManager, EventQueue<LayoutManager>) {
+        let mut manager = manager_with(options);
+        let event_queue = manager.connect_to(river.connect()).unwrap();
+        (manager, event_queue)
+    }
+
+    /// Handle events
+    /// until `done` returns `true`.
+    fn dispatch_until(
+        manager: &mut LayoutManager,
+        event_queue: &mut EventQueue<LayoutManager>,
+        mut done: impl FnMut(&LayoutManager) -> bool,
+    ) {
+        let deadline = Instant::now() + Duration::from_secs(120);
+        while !done(manager) {
+            assert!(Instant::now() < deadline, "timed out");
+            event_queue.roundtrip(manager).unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn finished_layout(manager: &LayoutManager, tags: u32, count: usize) -> Option<Vec<Rect>> {
+        match manager.gen.try_layout(
+            "DP-1",
+            tags,
+            Size::new_checked(CONTAINER.0 as usize, CONTAINER.1 as usize),
+            count,
+        ) {
+            Status::Finished(layout) => Some(layout.to_vec()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn tag_option_uses_settings_of_river_tags() {
+        let options = options(&[
+            "--tag-setting",
+            "3 max_width 640",
+            "generate",
+            "--output",
+            "DP-1",
+            "--tag",
+            "3",
+            "1920x1080",
+            "2",
+        ]);
+        let Some(Action::Generate(GenerateArgs { layout: args, .. })) = options.args.action.clone()
+        else {
+            panic!("action should be `generate`");
+        };
+        assert_eq!(args.tags(), 1 << 2);
+
+        let river = MockRiver::new(&["DP-1"]);
+        river.demand_layout("DP-1", 2, CONTAINER.0, CONTAINER.1, 1 << 2);
+        let (mut manager, mut event_queue) = connect_with(&river, options);
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            finished_layout(manager, 1 << 2, 2).is_some_and(|layout| {
+                river
+                    .commits("DP-1")
+                    .last()
+                    .is_some_and(|x| x.views == layout)
+            })
+        });
+        let layout = manager
+            .gen
+            .generate(&args.output, args.tags(), args.container, args.count)
+            .unwrap();
+        assert!(layout.iter().all(|x| x.width().get() <= 640));
+        assert_eq!(river.commits("DP-1").last().unwrap().views, layout);
+    }
+
+    #[test]
+    fn layout_manager_commits_layout_for_demand() {
+        let river = MockRiver::new(&["DP-1"]);
+        river.demand_layout("DP-1", 3, CONTAINER.0, CONTAINER.1, 1);
+        let (mut manager, mut event_queue) = connect(&river);
+        dispatch_until(&mut manager, &mut event_queue, |_| {
+            !river.commits("DP-1").is_empty()
+        });
+        let commit = river.commits("DP-1").remove(0);
+        assert_eq!(commit.name, "owm");
+        assert_eq!(commit.views.len(), 3);
+        assert!(commit
+            .views
+            .iter()
+            .all(|x| x.right() <= CONTAINER.0 as usize && x.bottom() <= CONTAINER.1 as usize));
+    }
+
+    #[test]
+    fn layout_manager_retries_layout_when_finished() {
+        let river = MockRiver::new(&["DP-1"]);
+        river.demand_layout("DP-1", 2, CONTAINER.0, CONTAINER.1, 1);
+        let (mut manager, mut event_queue) = connect(&river);
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            finished_layout(manager, 1, 2).is_some_and(|layout| {
+                river
+                    .commits("DP-1")
+                    .last()
+                    .is_some_and(|x| x.views == layout)
+            })
+        });
+        assert!(river.commands().contains(&vec![
+            "send-layout-cmd".to_owned(),
+            "owm".to_owned(),
+            "retry-layout".to_owned()
+        ]));
+    }
+
+    #[test]
+    fn layout_manager_applies_user_commands() {
+        let river = MockRiver::new(&["DP-1"]);
+        river.demand_layout("DP-1", 2, CONTAINER.0, CONTAINER.1, 1 << 1);
+        let (mut manager, mut event_queue) = connect(&river);
+        dispatch_until(&mut manager, &mut event_queue, |_| {
+            !river.commits("DP-1").is_empty()
+        });
+        river.send_layout_cmd("owm", "set-for-tags max_width 640");
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            finished_layout(manager, 1 << 1, 2).is_some_and(|layout| {
+                river
+                    .commits("DP-1")
+                    .last()
+                    .is_some_and(|x| x.views == layout)
+            })
+        });
+        assert!(river
+            .commits("DP-1")
+            .last()
+            .unwrap()
+            .views
+            .iter()
+            .all(|x| x.width().get() <= 640));
+    }
+
+    #[test]
+    fn layout_manager_reports_namespace_in_use() {
+        let river = MockRiver::new(&["DP-1"]);
+        river.demand_layout("DP-1", 1, CONTAINER.0, CONTAINER.1, 1);
+        let (mut manager, mut event_queue) = connect(&river);
+        dispatch_until(&mut manager, &mut event_queue, |_| {
+            !river.commits("DP-1").is_empty()
+        });
+        let (mut other, mut other_event_queue) = connect(&river);
+        dispatch_until(&mut other, &mut other_event_queue, |other| {
+            matches!(other.error, Some(Error::NamespaceInUse(_)))
+        });
+    }
+
+    #[test]
+    fn layout_manager_survives_output_removal() {
+        let river = MockRiver::new(&["DP-1", "DP-2"]);
+        river.demand_layout("DP-1", 1, CONTAINER.0, CONTAINER.1, 1);
+        river.demand_layout("DP-2", 1, CONTAINER.0, CONTAINER.1, 1);
+        let (mut manager, mut event_queue) = connect(&river);
+        dispatch_until(&mut manager, &mut event_queue, |_| {
+            !river.commits("DP-1").is_empty() && !river.commits("DP-2").is_empty()
+        });
+        river.remove_output("DP-2");
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            manager.outputs.len() == 1 && river.layout_count("DP-2") == 0
+        });
+        river.demand_layout("DP-1", 2, CONTAINER.0, CONTAINER.1, 1);
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            finished_layout(manager, 1, 2).is_some_and(|layout| {
+                river
+                    .commits("DP-1")
+                    .last()
+                    .is_some_and(|x| x.views == layout)
+            })
+        });
+    }
+
+    #[test]
+    fn layout_manager_reconnects_after_losing_connection() {
+        let river = Arc::new(MockRiver::new(&["DP-1"]));
+        river.demand_layout("DP-1", 1, CONTAINER.0, CONTAINER.1, 1);
+        let manager = thread::spawn({
+            // The manager stops reconnecting
+            // once the compositor is gone.
+            let river = Arc::downgrade(&river);
+            move || {
+                manager_with(options(&["--reconnect-timeout", "1"])).run(|| {
+                    river
+                        .upgrade()
+                        .map(|river| river.connect())
+                        .ok_or(Error::Connect(ConnectError::NoCompositor))
+                })
+            }
+        });
+        wait_until(|| !river.commits("DP-1").is_empty());
+
+        river.disconnect_clients();
+        // Only a new connection
+        // can lay out the new demand.
+        river.demand_layout("DP-1", 2, CONTAINER.0, CONTAINER.1, 1);
+        wait_until(|| {
+            river
+                .commits("DP-1")
+                .last()
+                .is_some_and(|x| x.views.len() == 2)
+        });
+
+        drop(river);
+        assert!(manager
+            .join()
+            .unwrap()
+            .is_err_and(|e| matches!(e, Error::Connect(_))));
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_config_file() {
+        let config = r#"
+            namespace = "owm2"
+            precompute_up_to = 6
+            anytime = true
+            reconnect_timeout = 5
+            cache_file = "/tmp/owm-layouts"
+            gaps_weight = 2
+        "#
+        .parse::<ConfigFile>()
+        .unwrap();
+        let options = options(&["--reconnect-timeout", "1"]);
+        assert_eq!(options.namespace(&config), "owm2");
+        let run_options = options.run_options(&config);
+        assert_eq!(run_options.precompute_up_to, 0);
+        assert!(run_options.anytime);
+        assert_eq!(run_options.reconnect_timeout, Duration::from_secs(1));
+        assert_eq!(run_options.cache_path, None);
+
+        let options = Options::parse_from(["owm", "--cache-file", "/tmp/owm-other"]);
+        let config = "no_cache_file = true".parse::<ConfigFile>().unwrap();
+        assert_eq!(
+            options.run_options(&config).cache_path,
+            Some(PathBuf::from("/tmp/owm-other"))
+        );
+    }
+
+    #[test]
+    fn layout_manager_reloads_config_missed_while_disconnected() {
+        let path = std::env::temp_dir().join(format!(
+            "owm-test-{}-layout_manager_reloads_config_missed_while_disconnected.toml",
+            std::process::id()
+        ));
+        fs::write(&path, "").unwrap();
+        let mut options = options(&[]);
+        options.config_path = Some(path.clone());
+        let mut manager = manager_with(options);
+        watch_config(
+            path.clone(),
+            manager.sender.clone(),
+            Arc::clone(&manager.reload_pending),
+        );
+
+        fs::write(&path, "anytime = true\ndebug = true\nmax_width = 640").unwrap();
+        // Modification times may be too coarse
+        // to show the change otherwise.
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        // Commands sent before connecting are lost.
+        wait_until(|| manager.reload_pending.load(Ordering::Relaxed));
+        let river = MockRiver::new(&["DP-1"]);
+        let mut event_queue = manager.connect_to(river.connect()).unwrap();
+        dispatch_until(&mut manager, &mut event_queue, |manager| {
+            manager.run_options.anytime
+        });
+        assert!(manager.run_options.debug);
+        assert!(manager
+            .gen
+            .generate("DP-1", 1, Size::new_checked(1920, 1080), 1)
+            .unwrap()
+            .iter()
+            .all(|x| x.width().get() <= 640));
+    }
+
+    /// Wait until `done` returns `true`.
+    fn wait_until(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(120);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+mod protocol {
+    // See <https://docs.rs/wayland-scanner/latest/wayland_scanner/#example-usage>.
+
+    #![allow(non_upper_case_globals)]
+
+    #[allow(clippy::single_component_path_imports)] // Used by macros.
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __layout_interfaces {
+        use wayland_client::backend as wayland_backend;
+        use wayland_client::protocol::__interfaces::*;
+
+        wayland_scanner::generate_interfaces!("./protocols/river-layout-v3.xml");
+    }
+    use self::__layout_interfaces::*;
+
+    pub mod __control_interfaces {
+        use wayland_client::backend as wayland_backend;
+        use wayland_client::protocol::__interfaces::*;
+
+        wayland_scanner::generate_interfaces!("./protocols/river-control-unstable-v1.xml");
+    }
+    use self::__control_interfaces::*;
+
+    wayland_scanner::generate_client_code!("./protocols/river-layout-v3.xml");
+    wayland_scanner::generate_client_code!("./protocols/river-control-unstable-v1.xml");
+}
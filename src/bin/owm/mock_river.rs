@@ -0,0 +1,553 @@
+use std::ffi::CString;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use owm_problem::Rect;
+use wayland_backend::io_lifetimes::OwnedFd;
+use wayland_backend::protocol::{Argument, Message};
+use wayland_backend::server::{
+    Backend, ClientId, DisconnectReason, GlobalHandler, GlobalId, Handle, ObjectData, ObjectId,
+};
+use wayland_backend::smallvec::smallvec;
+use wayland_client::protocol::__interfaces::{WL_OUTPUT_INTERFACE, WL_SEAT_INTERFACE};
+use wayland_client::Connection;
+
+use crate::protocol::{
+    __control_interfaces::ZRIVER_CONTROL_V1_INTERFACE,
+    __layout_interfaces::RIVER_LAYOUT_MANAGER_V3_INTERFACE,
+};
+
+/// How often the compositor
+/// handles requests
+/// and sends events.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// In-process stand-in for River,
+/// serving `wl_seat`, `wl_output`,
+/// `river_layout_manager_v3`, and `zriver_control_v1`
+/// on a thread.
+///
+/// Like River,
+/// it demands layouts from layout objects,
+/// including new ones,
+/// and sends them layout commands,
+/// demanding a new layout after each.
+/// The first output is focused.
+/// Outputs can be removed,
+/// and clients disconnected.
+///
+/// The thread stops when this is dropped.
+pub struct MockRiver {
+    handle: Handle,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// A layout committed by a client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub serial: u32,
+    pub name: String,
+    pub views: Vec<Rect>,
+}
+
+#[derive(Default)]
+struct State {
+    outputs: Vec<Output>,
+    layouts: Vec<Layout>,
+    /// Layouts committed on each output,
+    /// kept after their layout objects are destroyed.
+    commits: Vec<(usize, Commit)>,
+    clients: Vec<ClientId>,
+    /// Arguments for the next command.
+    arguments: Vec<String>,
+    commands: Vec<Vec<String>>,
+    /// Command callbacks to report success to.
+    /// Success destroys the callback,
+    /// so it cannot be sent
+    /// while its request is handled.
+    callbacks: Vec<ObjectId>,
+    serial: u32,
+}
+
+struct Output {
+    name: String,
+    /// `None` once removed.
+    global: Option<GlobalId>,
+    /// `wl_output` objects bound by clients.
+    objects: Vec<ObjectId>,
+    /// Last demand for this output,
+    /// repeated after layout commands.
+    demand: Option<Demand>,
+}
+
+#[derive(Clone, Copy)]
+struct Demand {
+    count: u32,
+    width: u32,
+    height: u32,
+    tags: u32,
+}
+
+struct Layout {
+    id: ObjectId,
+    client: ClientId,
+    output: usize,
+    namespace: String,
+    /// Views pushed since the last commit.
+    views: Vec<Rect>,
+}
+
+impl MockRiver {
+    /// Start a compositor
+    /// with outputs named `outputs`.
+    pub fn new(outputs: &[&str]) -> Self {
+        let mut backend = Backend::<()>::new().expect("backend should initialize");
+        let handle = backend.handle();
+        let state = Arc::new(Mutex::new(State {
+            outputs: outputs
+                .iter()
+                .map(|name| Output {
+                    name: name.to_string(),
+                    global: None,
+                    objects: Vec::new(),
+                    demand: None,
+                })
+                .collect(),
+            ..State::default()
+        }));
+        handle.create_global::<()>(&WL_SEAT_INTERFACE, 7, Arc::new(Global::Seat));
+        for i in 0..outputs.len() {
+            state.lock().unwrap().outputs[i].global = Some(handle.create_global::<()>(
+                &WL_OUTPUT_INTERFACE,
+                4,
+                Arc::new(Global::Output(i, Arc::clone(&state))),
+            ));
+        }
+        handle.create_global::<()>(
+            &RIVER_LAYOUT_MANAGER_V3_INTERFACE,
+            2,
+            Arc::new(Global::LayoutManager(Arc::clone(&state))),
+        );
+        handle.create_global::<()>(
+            &ZRIVER_CONTROL_V1_INTERFACE,
+            1,
+            Arc::new(Global::Control(Arc::clone(&state))),
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let handle = handle.clone();
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let _ = backend.dispatch_all_clients(&mut ());
+                    let callbacks = std::mem::take(&mut state.lock().unwrap().callbacks);
+                    for callback in callbacks {
+                        let _ = handle.send_event(Message {
+                            sender_id: callback,
+                            opcode: 0,
+                            args: smallvec![string("")],
+                        });
+                    }
+                    let _ = backend.flush(None);
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+        });
+        Self {
+            handle,
+            state,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Return a connection to this compositor
+    /// for a new client.
+    pub fn connect(&self) -> Connection {
+        let (server, client) = UnixStream::pair().expect("socket pair should be created");
+        let id = self
+            .handle
+            .clone()
+            .insert_client(server, Arc::new(()))
+            .expect("client should be inserted");
+        self.state.lock().unwrap().clients.push(id);
+        Connection::from_socket(client).expect("client should connect")
+    }
+
+    /// Close connections to all clients,
+    /// like the compositor exiting.
+    pub fn disconnect_clients(&self) {
+        // Killing a client destroys its objects,
+        // which needs the state.
+        let clients = std::mem::take(&mut self.state.lock().unwrap().clients);
+        for client in clients {
+            self.handle
+                .kill_client(client, DisconnectReason::ConnectionClosed);
+        }
+    }
+
+    /// Remove the output named `output`,
+    /// like unplugging a monitor.
+    pub fn remove_output(&self, output: &str) {
+        let mut state = self.state.lock().unwrap();
+        let output = find_output(&state, output);
+        state.outputs[output].demand = None;
+        let global = state.outputs[output].global.take().unwrap();
+        self.handle.remove_global::<()>(global);
+    }
+
+    /// Demand a layout of `count` views
+    /// in a `width` by `height` area
+    /// from layouts on `output`,
+    /// with `tags` focused.
+    pub fn demand_layout(&self, output: &str, count: u32, width: u32, height: u32, tags: u32) {
+        let mut state = self.state.lock().unwrap();
+        let output = find_output(&state, output);
+        state.outputs[output].demand = Some(Demand {
+            count,
+            width,
+            height,
+            tags,
+        });
+        demand(&self.handle, &mut state, output);
+    }
+
+    /// Send `command` to layouts in `namespace`
+    /// on the focused output,
+    /// like `riverctl send-layout-cmd`.
+    pub fn send_layout_cmd(&self, namespace: &str, command: &str) {
+        send_layout_cmd(
+            &self.handle,
+            &mut self.state.lock().unwrap(),
+            namespace,
+            command,
+        );
+    }
+
+    /// Return layouts committed
+    /// on `output`,
+    /// oldest first.
+    pub fn commits(&self, output: &str) -> Vec<Commit> {
+        let state = self.state.lock().unwrap();
+        state
+            .commits
+            .iter()
+            .filter(|(x, _)| state.outputs[*x].name == output)
+            .map(|(_, commit)| commit.clone())
+            .collect()
+    }
+
+    /// Return the number of layout objects
+    /// on `output`.
+    pub fn layout_count(&self, output: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .layouts
+            .iter()
+            .filter(|x| state.outputs[x.output].name == output)
+            .count()
+    }
+
+    /// Return commands run
+    /// through `zriver_control_v1`,
+    /// oldest first.
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.state.lock().unwrap().commands.clone()
+    }
+}
+
+impl Drop for MockRiver {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Return the index of the output
+/// named `name`,
+/// if not removed.
+fn find_output(state: &State, name: &str) -> usize {
+    state
+        .outputs
+        .iter()
+        .position(|x| x.name == name && x.global.is_some())
+        .expect("output should exist")
+}
+
+/// Send the last demand for `output`
+/// to its layouts.
+fn demand(handle: &Handle, state: &mut State, output: usize) {
+    let Some(demand) = state.outputs[output].demand else {
+        return;
+    };
+    state.serial += 1;
+    for layout in state.layouts.iter_mut().filter(|x| x.output == output) {
+        layout.views.clear();
+        send_demand(handle, layout.id.clone(), demand, state.serial);
+    }
+}
+
+fn send_demand(handle: &Handle, layout: ObjectId, demand: Demand, serial: u32) {
+    let _ = handle.send_event(Message {
+        sender_id: layout,
+        opcode: 1,
+        args: smallvec![
+            Argument::Uint(demand.count),
+            Argument::Uint(demand.width),
+            Argument::Uint(demand.height),
+            Argument::Uint(demand.tags),
+            Argument::Uint(serial),
+        ],
+    });
+}
+
+/// Send `command` to layouts in `namespace`
+/// on the focused output,
+/// then demand a new layout from them.
+fn send_layout_cmd(handle: &Handle, state: &mut State, namespace: &str, command: &str) {
+    let tags = state.outputs[0].demand.map_or(1, |x| x.tags);
+    for layout in state
+        .layouts
+        .iter()
+        .filter(|x| x.output == 0 && x.namespace == namespace)
+    {
+        let _ = handle.send_event(Message {
+            sender_id: layout.id.clone(),
+            opcode: 3,
+            args: smallvec![Argument::Uint(tags)],
+        });
+        let _ = handle.send_event(Message {
+            sender_id: layout.id.clone(),
+            opcode: 2,
+            args: smallvec![string(command)],
+        });
+    }
+    demand(handle, state, 0);
+}
+
+fn string(s: &str) -> Argument<ObjectId, i32> {
+    Argument::Str(Some(Box::new(
+        CString::new(s).expect("string should not contain nul"),
+    )))
+}
+
+fn string_arg(arg: &Argument<ObjectId, OwnedFd>) -> String {
+    match arg {
+        Argument::Str(Some(s)) => s.to_string_lossy().into_owned(),
+        _ => panic!("argument should be a string"),
+    }
+}
+
+fn uint_arg(arg: &Argument<ObjectId, OwnedFd>) -> u32 {
+    match arg {
+        Argument::Uint(x) => *x,
+        _ => panic!("argument should be a uint"),
+    }
+}
+
+fn int_arg(arg: &Argument<ObjectId, OwnedFd>) -> i32 {
+    match arg {
+        Argument::Int(x) => *x,
+        _ => panic!("argument should be an int"),
+    }
+}
+
+fn object_arg(arg: &Argument<ObjectId, OwnedFd>) -> ObjectId {
+    match arg {
+        Argument::Object(x) | Argument::NewId(x) => x.clone(),
+        _ => panic!("argument should be an object"),
+    }
+}
+
+enum Global {
+    Seat,
+    Output(usize, Arc<Mutex<State>>),
+    LayoutManager(Arc<Mutex<State>>),
+    Control(Arc<Mutex<State>>),
+}
+
+impl GlobalHandler<()> for Global {
+    fn bind(
+        self: Arc<Self>,
+        handle: &Handle,
+        _: &mut (),
+        _: ClientId,
+        _: GlobalId,
+        object_id: ObjectId,
+    ) -> Arc<dyn ObjectData<()>> {
+        match &*self {
+            Global::Seat => Arc::new(Ignore),
+            Global::Output(i, state) => {
+                let mut state = state.lock().unwrap();
+                state.outputs[*i].objects.push(object_id.clone());
+                // `name` was added in version 4.
+                if handle
+                    .object_info(object_id.clone())
+                    .is_ok_and(|x| x.version >= 4)
+                {
+                    let _ = handle.send_event(Message {
+                        sender_id: object_id.clone(),
+                        opcode: 4,
+                        args: smallvec![string(&state.outputs[*i].name)],
+                    });
+                }
+                let _ = handle.send_event(Message {
+                    sender_id: object_id,
+                    opcode: 2,
+                    args: smallvec![],
+                });
+                Arc::new(Ignore)
+            }
+            Global::LayoutManager(state) => Arc::new(LayoutManager(Arc::clone(state))),
+            Global::Control(state) => Arc::new(Control(Arc::clone(state))),
+        }
+    }
+}
+
+/// Object whose requests need no response.
+struct Ignore;
+
+impl ObjectData<()> for Ignore {
+    fn request(
+        self: Arc<Self>,
+        _: &Handle,
+        _: &mut (),
+        _: ClientId,
+        _: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        None
+    }
+
+    fn destroyed(&self, _: &mut (), _: ClientId, _: ObjectId) {}
+}
+
+struct LayoutManager(Arc<Mutex<State>>);
+
+impl ObjectData<()> for LayoutManager {
+    fn request(
+        self: Arc<Self>,
+        handle: &Handle,
+        _: &mut (),
+        client: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        // Opcode 0 is `destroy`.
+        if msg.opcode != 1 {
+            return None;
+        }
+        let id = object_arg(&msg.args[0]);
+        let output_id = object_arg(&msg.args[1]);
+        let namespace = string_arg(&msg.args[2]);
+        let mut state = self.0.lock().unwrap();
+        let output = state
+            .outputs
+            .iter()
+            .position(|x| x.objects.contains(&output_id))
+            .expect("output should be bound");
+        if state
+            .layouts
+            .iter()
+            .any(|x| x.output == output && x.namespace == namespace && x.client != client)
+        {
+            let _ = handle.send_event(Message {
+                sender_id: id.clone(),
+                opcode: 0,
+                args: smallvec![],
+            });
+        } else if let Some(demand) = state.outputs[output].demand {
+            state.serial += 1;
+            send_demand(handle, id.clone(), demand, state.serial);
+        }
+        state.layouts.push(Layout {
+            id,
+            client,
+            output,
+            namespace,
+            views: Vec::new(),
+        });
+        Some(Arc::new(LayoutObject(Arc::clone(&self.0))))
+    }
+
+    fn destroyed(&self, _: &mut (), _: ClientId, _: ObjectId) {}
+}
+
+struct LayoutObject(Arc<Mutex<State>>);
+
+impl ObjectData<()> for LayoutObject {
+    fn request(
+        self: Arc<Self>,
+        _: &Handle,
+        _: &mut (),
+        _: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        let mut state = self.0.lock().unwrap();
+        let state = &mut *state;
+        let layout = state.layouts.iter_mut().find(|x| x.id == msg.sender_id)?;
+        match msg.opcode {
+            1 => layout.views.push(Rect::new_checked(
+                int_arg(&msg.args[0]) as usize,
+                int_arg(&msg.args[1]) as usize,
+                uint_arg(&msg.args[2]) as usize,
+                uint_arg(&msg.args[3]) as usize,
+            )),
+            2 => {
+                let commit = Commit {
+                    serial: uint_arg(&msg.args[1]),
+                    name: string_arg(&msg.args[0]),
+                    views: std::mem::take(&mut layout.views),
+                };
+                state.commits.push((layout.output, commit));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn destroyed(&self, _: &mut (), _: ClientId, object_id: ObjectId) {
+        self.0.lock().unwrap().layouts.retain(|x| x.id != object_id);
+    }
+}
+
+struct Control(Arc<Mutex<State>>);
+
+impl ObjectData<()> for Control {
+    fn request(
+        self: Arc<Self>,
+        handle: &Handle,
+        _: &mut (),
+        _: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        let mut state = self.0.lock().unwrap();
+        match msg.opcode {
+            1 => {
+                state.arguments.push(string_arg(&msg.args[0]));
+                None
+            }
+            2 => {
+                let arguments = std::mem::take(&mut state.arguments);
+                if let [command, namespace, layout_command] = &arguments[..] {
+                    if command == "send-layout-cmd" {
+                        send_layout_cmd(handle, &mut state, namespace, layout_command);
+                    }
+                }
+                state.commands.push(arguments);
+                state.callbacks.push(object_arg(&msg.args[1]));
+                Some(Arc::new(Ignore))
+            }
+            _ => None,
+        }
+    }
+
+    fn destroyed(&self, _: &mut (), _: ClientId, _: ObjectId) {}
+}
@@ -0,0 +1,401 @@
+use std::{num::NonZeroUsize, str::FromStr, time::Duration};
+
+use owm_problem::{
+    optimizer::{
+        AdjustRate, Algorithm, ConvergedThreshold, MutationAdjustRate, MutationChance,
+        SamplesPerCore,
+    },
+    AreaRatio, AspectRatio, Weight,
+};
+
+/// A command sent by the user
+/// using `riverctl send-layout-cmd NAMESPACE COMMAND`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Do nothing.
+    /// River sends a new layout demand
+    /// after every command.
+    RetryLayout,
+    /// Change a layout generator setting.
+    Set(Setting),
+    /// Change a layout generator setting
+    /// for the output receiving the command.
+    SetForOutput(Setting),
+    /// Remove settings specific to
+    /// the output receiving the command.
+    ResetForOutput,
+    /// Change a layout generator setting
+    /// for the tags focused when the command was sent.
+    SetForTags(Setting),
+    /// Remove settings specific to
+    /// the tags focused when the command was sent.
+    ResetForTags,
+    /// Read the configuration file again.
+    ReloadConfig,
+    /// Generate the layout last demanded
+    /// for the output receiving the command
+    /// again,
+    /// with a different seed.
+    Reroll,
+    /// Use the next variant
+    /// of the layout last demanded
+    /// for the output receiving the command.
+    NextVariant,
+    /// Use the previous variant
+    /// of the layout last demanded
+    /// for the output receiving the command.
+    PrevVariant,
+}
+
+/// A layout generator setting
+/// that can be changed at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Setting {
+    MinWidth(NonZeroUsize),
+    MinHeight(NonZeroUsize),
+    MaxWidth(Option<NonZeroUsize>),
+    MaxHeight(Option<NonZeroUsize>),
+    OverlapBordersBy(usize),
+    GapsWeight(Weight),
+    OverlapWeight(Weight),
+    AreaRatios(Vec<AreaRatio>),
+    AreaRatiosWeight(Weight),
+    AspectRatios(Vec<AspectRatio>),
+    AspectRatiosWeight(Weight),
+    AdjacentCloseWeight(Weight),
+    ReadingOrderWeight(Weight),
+    CenterMainWeight(Weight),
+    ConsistencyWeight(Weight),
+    Seed(u64),
+    Variants(NonZeroUsize),
+    Algorithm(Algorithm),
+    SamplesPerCore(SamplesPerCore),
+    AdjustRate(AdjustRate),
+    MutationChance(MutationChance),
+    MutationAdjustRate(MutationAdjustRate),
+    ConvergedThreshold(ConvergedThreshold),
+    /// Milliseconds when parsed.
+    TimeBudget(Option<Duration>),
+}
+
+/// Error returned when failing to parse a command.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseCommandError {
+    /// Command is empty.
+    #[error("command is empty")]
+    Empty,
+    /// Command is not recognized.
+    #[error("unknown command '{0}'")]
+    Unknown(String),
+    /// Command has invalid arguments.
+    #[error("{0}")]
+    Setting(#[from] ParseSettingError),
+}
+
+/// Error returned when a tag number is invalid.
+#[derive(Clone, Debug, thiserror::Error, PartialEq)]
+#[error("tag must be from 1 to {}, got '{0}'", u32::BITS)]
+pub struct ParseTagError(String);
+
+/// Error returned when failing to parse a setting.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseSettingError {
+    /// Setting name is missing.
+    #[error("missing setting name")]
+    MissingName,
+    /// Setting name is not recognized.
+    #[error("unknown setting '{0}'")]
+    UnknownName(String),
+    /// Setting value is missing or invalid.
+    #[error("invalid value '{value}' for '{name}': {reason}")]
+    InvalidValue {
+        name: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl FromStr for Command {
+    type Err = ParseCommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, args) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        match command {
+            "" => Err(ParseCommandError::Empty),
+            "retry-layout" => Ok(Command::RetryLayout),
+            "set" => Ok(Command::Set(args.parse()?)),
+            "set-for-output" => Ok(Command::SetForOutput(args.parse()?)),
+            "reset-for-output" => Ok(Command::ResetForOutput),
+            "set-for-tags" => Ok(Command::SetForTags(args.parse()?)),
+            "reset-for-tags" => Ok(Command::ResetForTags),
+            "reload-config" => Ok(Command::ReloadConfig),
+            "reroll" => Ok(Command::Reroll),
+            "next-variant" => Ok(Command::NextVariant),
+            "prev-variant" => Ok(Command::PrevVariant),
+            _ => Err(ParseCommandError::Unknown(command.to_owned())),
+        }
+    }
+}
+
+impl FromStr for Setting {
+    type Err = ParseSettingError;
+
+    /// Parse a setting from `NAME VALUE`.
+    /// Names match command-line options,
+    /// with `_` in place of `-`.
+    /// Lists are comma-separated.
+    /// An empty value unsets optional settings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, value) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let value = value.trim();
+        let invalid = |reason: String| ParseSettingError::InvalidValue {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            reason,
+        };
+        match name {
+            "" => Err(ParseSettingError::MissingName),
+            "min_width" => parse(value).map(Setting::MinWidth).map_err(invalid),
+            "min_height" => parse(value).map(Setting::MinHeight).map_err(invalid),
+            "max_width" => parse_option(value).map(Setting::MaxWidth).map_err(invalid),
+            "max_height" => parse_option(value).map(Setting::MaxHeight).map_err(invalid),
+            "overlap_borders_by" => parse(value).map(Setting::OverlapBordersBy).map_err(invalid),
+            "gaps_weight" => parse(value).map(Setting::GapsWeight).map_err(invalid),
+            "overlap_weight" => parse(value).map(Setting::OverlapWeight).map_err(invalid),
+            "area_ratios" => parse_list(value).map(Setting::AreaRatios).map_err(invalid),
+            "area_ratios_weight" => parse(value).map(Setting::AreaRatiosWeight).map_err(invalid),
+            "aspect_ratios" => parse_list(value)
+                .map(Setting::AspectRatios)
+                .map_err(invalid),
+            "aspect_ratios_weight" => parse(value)
+                .map(Setting::AspectRatiosWeight)
+                .map_err(invalid),
+            "adjacent_close_weight" => parse(value)
+                .map(Setting::AdjacentCloseWeight)
+                .map_err(invalid),
+            "reading_order_weight" => parse(value)
+                .map(Setting::ReadingOrderWeight)
+                .map_err(invalid),
+            "center_main_weight" => parse(value).map(Setting::CenterMainWeight).map_err(invalid),
+            "consistency_weight" => parse(value)
+                .map(Setting::ConsistencyWeight)
+                .map_err(invalid),
+            "seed" => parse(value).map(Setting::Seed).map_err(invalid),
+            "variants" => parse(value).map(Setting::Variants).map_err(invalid),
+            "algorithm" => parse(value).map(Setting::Algorithm).map_err(invalid),
+            "samples_per_core" => parse(value).map(Setting::SamplesPerCore).map_err(invalid),
+            "adjust_rate" => parse(value).map(Setting::AdjustRate).map_err(invalid),
+            "mutation_chance" => parse(value).map(Setting::MutationChance).map_err(invalid),
+            "mutation_adjust_rate" => parse(value)
+                .map(Setting::MutationAdjustRate)
+                .map_err(invalid),
+            "converged_threshold" => parse(value)
+                .map(Setting::ConvergedThreshold)
+                .map_err(invalid),
+            "time_budget" => parse_option(value)
+                .map(|x| Setting::TimeBudget(x.map(Duration::from_millis)))
+                .map_err(invalid),
+            _ => Err(ParseSettingError::UnknownName(name.to_owned())),
+        }
+    }
+}
+
+impl Setting {
+    /// Return the name of this setting,
+    /// as used by `FromStr`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::MinWidth(_) => "min_width",
+            Setting::MinHeight(_) => "min_height",
+            Setting::MaxWidth(_) => "max_width",
+            Setting::MaxHeight(_) => "max_height",
+            Setting::OverlapBordersBy(_) => "overlap_borders_by",
+            Setting::GapsWeight(_) => "gaps_weight",
+            Setting::OverlapWeight(_) => "overlap_weight",
+            Setting::AreaRatios(_) => "area_ratios",
+            Setting::AreaRatiosWeight(_) => "area_ratios_weight",
+            Setting::AspectRatios(_) => "aspect_ratios",
+            Setting::AspectRatiosWeight(_) => "aspect_ratios_weight",
+            Setting::AdjacentCloseWeight(_) => "adjacent_close_weight",
+            Setting::ReadingOrderWeight(_) => "reading_order_weight",
+            Setting::CenterMainWeight(_) => "center_main_weight",
+            Setting::ConsistencyWeight(_) => "consistency_weight",
+            Setting::Seed(_) => "seed",
+            Setting::Variants(_) => "variants",
+            Setting::Algorithm(_) => "algorithm",
+            Setting::SamplesPerCore(_) => "samples_per_core",
+            Setting::AdjustRate(_) => "adjust_rate",
+            Setting::MutationChance(_) => "mutation_chance",
+            Setting::MutationAdjustRate(_) => "mutation_adjust_rate",
+            Setting::ConvergedThreshold(_) => "converged_threshold",
+            Setting::TimeBudget(_) => "time_budget",
+        }
+    }
+}
+
+/// Parse a tag number,
+/// starting from 1,
+/// into a River tags bitfield.
+pub fn parse_tag(s: &str) -> Result<u32, ParseTagError> {
+    s.parse::<u32>()
+        .ok()
+        .filter(|x| (1..=u32::BITS).contains(x))
+        .map(|x| 1 << (x - 1))
+        .ok_or_else(|| ParseTagError(s.to_owned()))
+}
+
+fn parse<T>(s: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    s.parse().map_err(|e: T::Err| e.to_string())
+}
+
+fn parse_option<T>(s: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        parse(s).map(Some)
+    }
+}
+
+fn parse_list<T>(s: &str) -> Result<Vec<T>, String>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    s.split(',').map(|x| parse(x.trim())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_parses_retry_layout() {
+        assert_eq!(
+            "retry-layout".parse::<Command>().unwrap(),
+            Command::RetryLayout
+        );
+    }
+
+    #[test]
+    fn command_parses_reroll() {
+        assert_eq!("reroll".parse::<Command>().unwrap(), Command::Reroll);
+    }
+
+    #[test]
+    fn command_parses_variant_commands() {
+        assert_eq!(
+            "next-variant".parse::<Command>().unwrap(),
+            Command::NextVariant
+        );
+        assert_eq!(
+            "prev-variant".parse::<Command>().unwrap(),
+            Command::PrevVariant
+        );
+    }
+
+    #[test]
+    fn command_parses_set_weight() {
+        assert_eq!(
+            "set center_main_weight 2.5".parse::<Command>().unwrap(),
+            Command::Set(Setting::CenterMainWeight(Weight::new(2.5).unwrap()))
+        );
+    }
+
+    #[test]
+    fn command_parses_set_ratios() {
+        assert_eq!(
+            "set area_ratios 2, 1".parse::<Command>().unwrap(),
+            Command::Set(Setting::AreaRatios(vec![
+                AreaRatio::new(2.0).unwrap(),
+                AreaRatio::new(1.0).unwrap()
+            ]))
+        );
+    }
+
+    #[test]
+    fn command_parses_set_for_output() {
+        assert_eq!(
+            "set-for-output max_width 2560".parse::<Command>().unwrap(),
+            Command::SetForOutput(Setting::MaxWidth(NonZeroUsize::new(2560)))
+        );
+    }
+
+    #[test]
+    fn command_parses_set_for_tags() {
+        assert_eq!(
+            "set-for-tags aspect_ratios 1".parse::<Command>().unwrap(),
+            Command::SetForTags(Setting::AspectRatios(vec![AspectRatio::new(1.0).unwrap()]))
+        );
+    }
+
+    #[test]
+    fn command_parses_time_budget_in_milliseconds() {
+        assert_eq!(
+            "set time_budget 250".parse::<Command>().unwrap(),
+            Command::Set(Setting::TimeBudget(Some(Duration::from_millis(250))))
+        );
+    }
+
+    #[test]
+    fn command_parses_unset_option() {
+        assert_eq!(
+            "set max_width".parse::<Command>().unwrap(),
+            Command::Set(Setting::MaxWidth(None))
+        );
+    }
+
+    #[test]
+    fn setting_name_round_trips() {
+        for s in [
+            "min_width 1",
+            "max_height",
+            "overlap_borders_by 2",
+            "area_ratios 1",
+            "consistency_weight 1",
+            "seed 1",
+            "variants 3",
+            "converged_threshold 0.95",
+            "algorithm local-search",
+            "time_budget 500",
+        ] {
+            let setting = s.parse::<Setting>().unwrap();
+            assert_eq!(Some(setting.name()), s.split_whitespace().next());
+        }
+    }
+
+    #[test]
+    fn parse_tag_returns_bitfield() {
+        assert_eq!(parse_tag("1"), Ok(0b1));
+        assert_eq!(parse_tag("3"), Ok(0b100));
+        assert_eq!(parse_tag("32"), Ok(1 << 31));
+        assert!(parse_tag("0").is_err());
+        assert!(parse_tag("33").is_err());
+    }
+
+    #[test]
+    fn command_rejects_invalid_values() {
+        assert!("set gaps_weight -1".parse::<Command>().is_err());
+        assert!("set area_ratios 2,0.5".parse::<Command>().is_err());
+        assert!("set min_width 0".parse::<Command>().is_err());
+        assert!("set min_width".parse::<Command>().is_err());
+        assert!("set adjust_rate 0".parse::<Command>().is_err());
+        assert!("set converged_threshold 1".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn command_rejects_unknown_names() {
+        assert!("".parse::<Command>().is_err());
+        assert!("frobnicate".parse::<Command>().is_err());
+        assert!("set frobnicate 1".parse::<Command>().is_err());
+    }
+}
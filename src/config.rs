@@ -0,0 +1,297 @@
+use std::{collections::BTreeMap, num::NonZeroUsize, path::PathBuf, str::FromStr};
+
+use toml::{Table, Value};
+
+use crate::command::{parse_tag, ParseSettingError, ParseTagError, Setting};
+
+/// Contents of a configuration file.
+///
+/// Configuration files are TOML.
+/// Top-level keys are settings
+/// and other options,
+/// named like command-line options
+/// with `_` in place of `-`.
+/// Settings for specific outputs
+/// go in `[outputs.OUTPUT]` tables,
+/// and settings for specific tags
+/// go in `[tags.TAG]` tables,
+/// where `TAG` is the tag number,
+/// starting from 1.
+///
+/// ```toml
+/// namespace = "owm"
+/// precompute_up_to = 6
+/// anytime = true
+/// max_width = 1920
+/// # An empty string unsets an optional setting.
+/// max_height = ""
+/// area_ratios = [3, 2, 1]
+///
+/// [outputs.DP-1]
+/// max_width = 2560
+///
+/// [tags.2]
+/// area_ratios = [1]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    pub namespace: Option<String>,
+    pub precompute_up_to: Option<usize>,
+    pub anytime: Option<bool>,
+    /// `Some(None)` means no limit.
+    pub max_cached_layouts: Option<Option<NonZeroUsize>>,
+    pub cache_file: Option<PathBuf>,
+    pub no_cache_file: Option<bool>,
+    pub reconnect_timeout: Option<u64>,
+    pub numbered_namespace: Option<bool>,
+    pub debug: Option<bool>,
+    pub settings: Vec<Setting>,
+    pub outputs: BTreeMap<String, Vec<Setting>>,
+    /// Settings for tags,
+    /// keyed by River tags bitfield.
+    pub tags: BTreeMap<u32, Vec<Setting>>,
+}
+
+/// Error returned when failing to parse a configuration file.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseConfigFileError {
+    /// File is not valid TOML.
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+    /// Value has the wrong type.
+    #[error("'{0}' must be {1}")]
+    WrongType(String, &'static str),
+    /// Tag is invalid.
+    #[error("invalid key in 'tags': {0}")]
+    Tag(#[from] ParseTagError),
+    /// Setting is invalid.
+    #[error("{0}")]
+    Setting(#[from] ParseSettingError),
+}
+
+impl FromStr for ConfigFile {
+    type Err = ParseConfigFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = ConfigFile::default();
+        for (key, value) in s.parse::<Table>()? {
+            match key.as_str() {
+                "namespace" => config.namespace = Some(into_string(key, value)?),
+                "precompute_up_to" => {
+                    config.precompute_up_to = Some(into_integer(key, value, "an integer >= 0")?)
+                }
+                "anytime" => config.anytime = Some(into_bool(key, value)?),
+                "max_cached_layouts" => {
+                    config.max_cached_layouts = Some(match value {
+                        // An empty string means no limit,
+                        // like for optional settings.
+                        Value::String(x) if x.is_empty() => None,
+                        x => Some(into_integer(key, x, "an integer > 0 or \"\"")?),
+                    })
+                }
+                "cache_file" => config.cache_file = Some(into_string(key, value)?.into()),
+                "no_cache_file" => config.no_cache_file = Some(into_bool(key, value)?),
+                "reconnect_timeout" => {
+                    config.reconnect_timeout = Some(into_integer(key, value, "an integer >= 0")?)
+                }
+                "numbered_namespace" => config.numbered_namespace = Some(into_bool(key, value)?),
+                "debug" => config.debug = Some(into_bool(key, value)?),
+                "outputs" => {
+                    for (output, value) in into_table(key, value)? {
+                        config
+                            .outputs
+                            .insert(output.clone(), parse_settings(into_table(output, value)?)?);
+                    }
+                }
+                "tags" => {
+                    for (tag, value) in into_table(key, value)? {
+                        config
+                            .tags
+                            .insert(parse_tag(&tag)?, parse_settings(into_table(tag, value)?)?);
+                    }
+                }
+                _ => config.settings.push(parse_setting(key, value)?),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn into_string(key: String, value: Value) -> Result<String, ParseConfigFileError> {
+    match value {
+        Value::String(x) => Ok(x),
+        _ => Err(ParseConfigFileError::WrongType(key, "a string")),
+    }
+}
+
+fn into_bool(key: String, value: Value) -> Result<bool, ParseConfigFileError> {
+    match value {
+        Value::Boolean(x) => Ok(x),
+        _ => Err(ParseConfigFileError::WrongType(key, "a boolean")),
+    }
+}
+
+fn into_integer<T>(
+    key: String,
+    value: Value,
+    expected: &'static str,
+) -> Result<T, ParseConfigFileError>
+where
+    T: FromStr,
+{
+    match value {
+        Value::Integer(x) => x.to_string().parse().ok(),
+        _ => None,
+    }
+    .ok_or(ParseConfigFileError::WrongType(key, expected))
+}
+
+fn into_table(key: String, value: Value) -> Result<Table, ParseConfigFileError> {
+    match value {
+        Value::Table(x) => Ok(x),
+        _ => Err(ParseConfigFileError::WrongType(key, "a table")),
+    }
+}
+
+fn parse_settings(table: Table) -> Result<Vec<Setting>, ParseConfigFileError> {
+    table
+        .into_iter()
+        .map(|(key, value)| parse_setting(key, value))
+        .collect()
+}
+
+fn parse_setting(key: String, value: Value) -> Result<Setting, ParseConfigFileError> {
+    let value = match value {
+        Value::Array(xs) => xs
+            .into_iter()
+            .map(|x| setting_value(&key, x))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(","),
+        x => setting_value(&key, x)?,
+    };
+    Ok(format!("{key} {value}").parse()?)
+}
+
+fn setting_value(key: &str, value: Value) -> Result<String, ParseConfigFileError> {
+    match value {
+        Value::String(x) => Ok(x),
+        Value::Integer(x) => Ok(x.to_string()),
+        Value::Float(x) => Ok(x.to_string()),
+        _ => Err(ParseConfigFileError::WrongType(
+            key.to_owned(),
+            "a string, number, or array of numbers",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use owm_problem::{AreaRatio, Weight};
+
+    use super::*;
+
+    #[test]
+    fn config_parses_settings_outputs_and_tags() {
+        let config = r#"
+            namespace = "owm2"
+            max_width = 1920
+            max_height = ""
+            gaps_weight = 2.5
+            area_ratios = [3, 2, 1.5]
+
+            [outputs.DP-1]
+            max_width = 2560
+
+            [tags.2]
+            area_ratios = [1]
+        "#
+        .parse::<ConfigFile>()
+        .unwrap();
+        assert_eq!(config.namespace.as_deref(), Some("owm2"));
+        assert_eq!(
+            config.settings,
+            vec![
+                Setting::AreaRatios(vec![
+                    AreaRatio::new(3.0).unwrap(),
+                    AreaRatio::new(2.0).unwrap(),
+                    AreaRatio::new(1.5).unwrap(),
+                ]),
+                Setting::GapsWeight(Weight::new(2.5).unwrap()),
+                Setting::MaxHeight(None),
+                Setting::MaxWidth(NonZeroUsize::new(1920)),
+            ]
+        );
+        assert_eq!(
+            config.outputs,
+            BTreeMap::from([(
+                "DP-1".to_owned(),
+                vec![Setting::MaxWidth(NonZeroUsize::new(2560))]
+            )])
+        );
+        assert_eq!(
+            config.tags,
+            BTreeMap::from([(
+                0b10,
+                vec![Setting::AreaRatios(vec![AreaRatio::new(1.0).unwrap()])]
+            )])
+        );
+    }
+
+    #[test]
+    fn config_rejects_invalid_settings() {
+        assert!("frobnicate = 1".parse::<ConfigFile>().is_err());
+        assert!("gaps_weight = -1".parse::<ConfigFile>().is_err());
+        assert!("gaps_weight = true".parse::<ConfigFile>().is_err());
+        assert!("namespace = 1".parse::<ConfigFile>().is_err());
+        assert!("outputs = 1".parse::<ConfigFile>().is_err());
+        assert!("[tags.0]\ngaps_weight = 1".parse::<ConfigFile>().is_err());
+    }
+
+    #[test]
+    fn config_parses_options() {
+        let config = r#"
+            precompute_up_to = 6
+            anytime = true
+            max_cached_layouts = ""
+            cache_file = "/tmp/owm-layouts"
+            no_cache_file = false
+            reconnect_timeout = 10
+            numbered_namespace = true
+            debug = true
+        "#
+        .parse::<ConfigFile>()
+        .unwrap();
+        assert_eq!(
+            config,
+            ConfigFile {
+                precompute_up_to: Some(6),
+                anytime: Some(true),
+                max_cached_layouts: Some(None),
+                cache_file: Some("/tmp/owm-layouts".into()),
+                no_cache_file: Some(false),
+                reconnect_timeout: Some(10),
+                numbered_namespace: Some(true),
+                debug: Some(true),
+                ..ConfigFile::default()
+            }
+        );
+        assert_eq!(
+            "max_cached_layouts = 100"
+                .parse::<ConfigFile>()
+                .unwrap()
+                .max_cached_layouts,
+            Some(NonZeroUsize::new(100))
+        );
+    }
+
+    #[test]
+    fn config_rejects_invalid_options() {
+        assert!("precompute_up_to = -1".parse::<ConfigFile>().is_err());
+        assert!("anytime = 1".parse::<ConfigFile>().is_err());
+        assert!("max_cached_layouts = 0".parse::<ConfigFile>().is_err());
+        assert!("cache_file = true".parse::<ConfigFile>().is_err());
+        assert!("[outputs.DP-1]\nanytime = true"
+            .parse::<ConfigFile>()
+            .is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    num::NonZeroUsize,
+    os::unix::{fs::MetadataExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use owm_problem::{Rect, Size};
+
+use crate::Key;
+
+/// Version of the cache file format
+/// and of how layouts are generated,
+/// included in configuration hashes.
+///
+/// Increment it when either changes
+/// in a way the package version may not reflect,
+/// so old layouts are not reused.
+const FORMAT_VERSION: u32 = 1;
+
+/// Most layouts kept
+/// when compacting the cache file.
+///
+/// Layouts saved least recently are dropped first,
+/// so layouts for configurations no longer used
+/// do not accumulate.
+const MAX_SAVED_LAYOUTS: usize = 10_000;
+
+/// Layouts saved to a file,
+/// so they survive restarts.
+///
+/// Each line of the file is one layout:
+/// the hash of the configuration that generated it,
+/// in hexadecimal,
+/// the container width and height,
+/// and the x, y, width, and height of each window,
+/// separated by spaces.
+/// Later lines replace earlier lines
+/// for the same configuration, container, and number of windows.
+///
+/// Only where lines start is kept in memory,
+/// indexed by configuration hash,
+/// and layouts are read when requested.
+///
+/// Every instance holds a shared lock on the file,
+/// so the file is only compacted
+/// when no other instance uses it.
+#[derive(Debug)]
+pub struct DiskCache {
+    path: PathBuf,
+    /// Also holds the lock.
+    file: Mutex<File>,
+    /// Offsets of lines
+    /// for each configuration hash,
+    /// oldest first.
+    offsets: Mutex<HashMap<u64, Vec<u64>>>,
+}
+
+impl DiskCache {
+    /// Open or create the cache file at `path`,
+    /// compacting it
+    /// if no other instance has it open.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::open_with_max_layouts(path, MAX_SAVED_LAYOUTS)
+    }
+
+    fn open_with_max_layouts(path: &Path, max_layouts: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut compacted = false;
+        let file = loop {
+            let file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(path)?;
+            if !compacted && try_flock(&file, libc::LOCK_EX | libc::LOCK_NB)? {
+                compact(path, &file, max_layouts)?;
+                compacted = true;
+                // The lock is on the replaced file.
+                continue;
+            }
+            flock(&file, libc::LOCK_SH)?;
+            // Another instance may have replaced the file
+            // while compacting.
+            let (x, y) = (file.metadata()?, fs::metadata(path)?);
+            if x.dev() == y.dev() && x.ino() == y.ino() {
+                break file;
+            }
+        };
+
+        let mut offsets = HashMap::<_, Vec<_>>::new();
+        let mut reader = BufReader::new(&file);
+        let mut offset = reader.seek(SeekFrom::Start(0))?;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line)?;
+            if len == 0 {
+                break;
+            }
+            // Invalid lines may come from a crash while writing.
+            if let Some((hash, _, _)) = parse_line(&line) {
+                offsets.entry(hash).or_default().push(offset);
+            }
+            offset += len as u64;
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::new(file),
+            offsets: Mutex::new(offsets),
+        })
+    }
+
+    /// Return layouts generated by the configuration
+    /// with hash `hash`.
+    pub fn layouts(&self, hash: u64) -> io::Result<Vec<(Key, Vec<Rect>)>> {
+        let Some(offsets) = self.offsets.lock().unwrap().get(&hash).cloned() else {
+            return Ok(Vec::new());
+        };
+        let mut layouts = HashMap::new();
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut line = String::new();
+        for offset in offsets {
+            reader.seek(SeekFrom::Start(offset))?;
+            line.clear();
+            reader.read_line(&mut line)?;
+            // An offset may be wrong
+            // if another instance wrote at the same time,
+            // so lines are checked.
+            if let Some((_, container, layout)) = parse_line(&line).filter(|(x, _, _)| *x == hash) {
+                layouts.insert((container, layout.len()), layout);
+            }
+        }
+        Ok(layouts.into_iter().collect())
+    }
+
+    /// Save a layout generated by the configuration
+    /// with hash `hash`.
+    pub fn insert(&self, hash: u64, container: Size, layout: &[Rect]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        let offset = file.seek(SeekFrom::End(0))?;
+        // Writing each line at once
+        // keeps lines intact
+        // when multiple instances share the file.
+        file.write_all(format_line(hash, container, layout).as_bytes())?;
+        self.offsets
+            .lock()
+            .unwrap()
+            .entry(hash)
+            .or_default()
+            .push(offset);
+        Ok(())
+    }
+}
+
+/// Replace the file at `path`,
+/// opened as `file`,
+/// with only the last valid line
+/// for each configuration, container, and number of windows,
+/// keeping at most `max_layouts` of the most recent.
+///
+/// The caller must hold an exclusive lock on `file`.
+fn compact(path: &Path, file: &File, max_layouts: usize) -> io::Result<()> {
+    let mut lines = HashMap::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if let Some((hash, container, layout)) = parse_line(&line) {
+            lines.insert((hash, container, layout.len()), (i, line));
+        }
+    }
+    let mut lines = lines.into_values().collect::<Vec<_>>();
+    lines.sort_unstable_by_key(|(i, _)| std::cmp::Reverse(*i));
+    lines.truncate(max_layouts);
+    lines.reverse();
+
+    // Writing a new file and renaming it
+    // keeps the old file intact
+    // if compacting fails.
+    // The name is unique to this process,
+    // so instances starting together
+    // do not write the same file.
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", std::process::id()));
+    let mut temp = BufWriter::new(File::create(&temp_path)?);
+    for (_, line) in lines {
+        writeln!(temp, "{line}")?;
+    }
+    temp.into_inner().map_err(|e| e.into_error())?;
+    fs::rename(&temp_path, path)
+}
+
+/// Lock `file`,
+/// blocking until it is available.
+fn flock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    // SAFETY: The descriptor is valid while `file` is borrowed.
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Lock `file` without blocking,
+/// returning whether it was locked.
+fn try_flock(file: &File, operation: libc::c_int) -> io::Result<bool> {
+    match flock(file, operation) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Return a hash of `config`,
+/// the version of this program,
+/// and `FORMAT_VERSION`,
+/// stable across runs.
+pub fn hash_config(config: &impl Debug) -> u64 {
+    // FNV-1a,
+    // see <http://www.isthe.com/chongo/tech/comp/fnv/index.html>.
+    format!("{} {FORMAT_VERSION} {config:?}", env!("CARGO_PKG_VERSION"))
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+fn format_line(hash: u64, container: Size, layout: &[Rect]) -> String {
+    let mut line = format!("{hash:016x} {} {}", container.width, container.height);
+    for rect in layout {
+        line.push_str(&format!(
+            " {} {} {} {}",
+            rect.x(),
+            rect.y(),
+            rect.width(),
+            rect.height()
+        ));
+    }
+    line.push('\n');
+    line
+}
+
+fn parse_line(line: &str) -> Option<(u64, Size, Vec<Rect>)> {
+    let mut words = line.split_whitespace();
+    let hash = u64::from_str_radix(words.next()?, 16).ok()?;
+    let numbers = words
+        .map(|x| x.parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    let (container, rects) = numbers.split_first_chunk::<2>()?;
+    let container = Size::new(
+        NonZeroUsize::new(container[0])?,
+        NonZeroUsize::new(container[1])?,
+    );
+    let rects = rects.chunks_exact(4);
+    if !rects.remainder().is_empty() {
+        return None;
+    }
+    rects
+        .map(|x| {
+            let rect = Rect::new(
+                x[0],
+                x[1],
+                NonZeroUsize::new(x[2])?,
+                NonZeroUsize::new(x[3])?,
+            );
+            (rect.right() <= container.width.get() && rect.bottom() <= container.height.get())
+                .then_some(rect)
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|layout| (hash, container, layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("owm-test-{}", std::process::id()))
+            .join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn disk_cache_loads_saved_layouts() {
+        let path = temp_path("disk_cache_loads_saved_layouts");
+        let container = Size::new_checked(100, 50);
+        let layout = vec![
+            Rect::new_checked(0, 0, 50, 50),
+            Rect::new_checked(50, 0, 50, 50),
+        ];
+        let disk_cache = DiskCache::open(&path).unwrap();
+        disk_cache.insert(1, container, &layout).unwrap();
+        disk_cache.insert(2, container, &layout[..1]).unwrap();
+        drop(disk_cache);
+        let disk_cache = DiskCache::open(&path).unwrap();
+        assert_eq!(
+            disk_cache.layouts(1).unwrap(),
+            vec![((container, 2), layout)]
+        );
+        assert_eq!(disk_cache.layouts(3).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn open_keeps_only_last_valid_line_per_layout() {
+        let path = temp_path("open_keeps_only_last_valid_line_per_layout");
+        let container = Size::new_checked(100, 50);
+        let old_layout = vec![Rect::new_checked(0, 0, 50, 50)];
+        let layout = vec![Rect::new_checked(0, 0, 100, 50)];
+        let disk_cache = DiskCache::open(&path).unwrap();
+        disk_cache.insert(1, container, &old_layout).unwrap();
+        disk_cache.insert(1, container, &layout).unwrap();
+        drop(disk_cache);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"1 100 50 0 0\n").unwrap();
+        drop(file);
+
+        let disk_cache = DiskCache::open(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format_line(1, container, &layout)
+        );
+        assert_eq!(
+            disk_cache.layouts(1).unwrap(),
+            vec![((container, 1), layout)]
+        );
+    }
+
+    #[test]
+    fn insert_is_visible_without_reopening() {
+        let path = temp_path("insert_is_visible_without_reopening");
+        let container = Size::new_checked(100, 50);
+        let layout = vec![Rect::new_checked(0, 0, 100, 50)];
+        let disk_cache = DiskCache::open(&path).unwrap();
+        disk_cache.insert(1, container, &layout).unwrap();
+        assert_eq!(
+            disk_cache.layouts(1).unwrap(),
+            vec![((container, 1), layout)]
+        );
+    }
+
+    #[test]
+    fn open_drops_least_recently_saved_layouts() {
+        let path = temp_path("open_drops_least_recently_saved_layouts");
+        let container = Size::new_checked(100, 50);
+        let layout = vec![Rect::new_checked(0, 0, 100, 50)];
+        let disk_cache = DiskCache::open(&path).unwrap();
+        disk_cache.insert(1, container, &layout).unwrap();
+        disk_cache.insert(2, container, &layout).unwrap();
+        disk_cache.insert(3, container, &layout).unwrap();
+        drop(disk_cache);
+
+        let disk_cache = DiskCache::open_with_max_layouts(&path, 2).unwrap();
+        assert_eq!(disk_cache.layouts(1).unwrap(), vec![]);
+        assert_eq!(
+            disk_cache.layouts(2).unwrap(),
+            vec![((container, 1), layout.clone())]
+        );
+        assert_eq!(
+            disk_cache.layouts(3).unwrap(),
+            vec![((container, 1), layout)]
+        );
+    }
+
+    #[test]
+    fn open_does_not_compact_file_in_use() {
+        let path = temp_path("open_does_not_compact_file_in_use");
+        let container = Size::new_checked(100, 50);
+        let layout = vec![Rect::new_checked(0, 0, 100, 50)];
+        let first = DiskCache::open(&path).unwrap();
+        first.insert(1, container, &layout).unwrap();
+        first.insert(1, container, &layout).unwrap();
+
+        let second = DiskCache::open_with_max_layouts(&path, 0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert_eq!(second.layouts(1).unwrap(), vec![((container, 1), layout)]);
+    }
+
+    #[test]
+    fn instances_sharing_file_keep_each_others_layouts() {
+        let path = temp_path("instances_sharing_file_keep_each_others_layouts");
+        let container = Size::new_checked(100, 50);
+        let layout = vec![Rect::new_checked(0, 0, 100, 50)];
+        let first = DiskCache::open(&path).unwrap();
+        let second = DiskCache::open(&path).unwrap();
+        first.insert(1, container, &layout).unwrap();
+        second.insert(2, container, &layout).unwrap();
+        first.insert(3, container, &layout).unwrap();
+        drop(first);
+        drop(second);
+
+        let disk_cache = DiskCache::open(&path).unwrap();
+        for hash in 1..=3 {
+            assert_eq!(
+                disk_cache.layouts(hash).unwrap(),
+                vec![((container, 1), layout.clone())]
+            );
+        }
+    }
+
+    #[test]
+    fn parse_line_round_trips() {
+        let container = Size::new_checked(100, 50);
+        let layout = vec![Rect::new_checked(1, 2, 3, 4)];
+        assert_eq!(
+            parse_line(&format_line(u64::MAX, container, &layout)),
+            Some((u64::MAX, container, layout))
+        );
+    }
+
+    #[test]
+    fn parse_line_rejects_invalid_layouts() {
+        assert_eq!(parse_line(""), None);
+        assert_eq!(parse_line("1 100"), None);
+        assert_eq!(parse_line("1 100 50 0 0 10"), None);
+        assert_eq!(parse_line("1 100 50 0 0 0 10"), None);
+        assert_eq!(parse_line("1 100 50 0 0 101 10"), None);
+        assert_eq!(parse_line("x 100 50 0 0 10 10"), None);
+    }
+}
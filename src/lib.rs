@@ -1,26 +1,182 @@
+mod command;
+mod config;
+mod disk_cache;
+mod scheduler;
+
+pub mod optimizer;
+
 use std::{
-    collections::hash_map::{Entry, HashMap},
+    collections::{
+        hash_map::{Entry, HashMap},
+        BTreeMap,
+    },
+    fmt, io,
+    iter::once,
+    mem::discriminant,
     num::NonZeroUsize,
-    sync::Arc,
-    thread,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use once_cell::sync::OnceCell;
-use optimal::{optimizer::derivative_free::pbil::*, prelude::*};
+use once_cell::sync::{Lazy, OnceCell};
 use owm_problem::{
-    encoding::Decoder, objective::Problem, post_processing::overlap_borders, AreaRatio,
-    AspectRatio, Rect, Size, Weights,
+    encoding::Decoder,
+    objective::{Objective, ObjectiveScore, Problem},
+    post_processing::overlap_borders,
+    templates::{self, main_stack, split_largest},
+    AreaRatio, AspectRatio, OptimizerConfig, Rect, Size, Weight, Weights,
 };
 use rand::prelude::*;
 use rand_xoshiro::SplitMix64;
-use rayon::prelude::*;
+
+use crate::{
+    disk_cache::{hash_config, DiskCache},
+    optimizer::Control,
+    scheduler::{Scheduler, Spawner},
+};
+
+pub use crate::{
+    command::{parse_tag, Command, ParseCommandError, ParseSettingError, ParseTagError, Setting},
+    config::{ConfigFile, ParseConfigFileError},
+};
 
 #[derive(Debug)]
 pub struct LayoutGen {
+    config: LayoutConfig,
+    profiles: HashMap<ProfileKey, Profile>,
+    disk_cache: Option<Arc<DiskCache>>,
+    max_cached_layouts: Option<NonZeroUsize>,
+    max_precomputed_count: usize,
+    /// Whether to publish improving layouts
+    /// before they finish.
+    anytime: bool,
+    /// Incremented on every use of the cache,
+    /// to find least recently used layouts.
+    clock: AtomicU64,
+    scheduler: Scheduler,
+    on_finish: OnFinish,
+    /// Profile, container, and number of windows
+    /// of the last layout demanded
+    /// for each output.
+    demanded: HashMap<String, (ProfileKey, Key)>,
+}
+
+/// Settings for all outputs and tags,
+/// and settings replacing them
+/// for specific outputs or tags.
+///
+/// Settings are validated as they are set.
+#[derive(Clone, Debug)]
+pub struct LayoutConfig {
+    defaults: RawLayoutGen,
+    overrides: Overrides,
+}
+
+/// Settings replacing defaults
+/// for specific outputs or tags.
+/// Tag settings take precedence over output settings.
+#[derive(Clone, Debug, Default)]
+struct Overrides {
+    outputs: BTreeMap<String, Vec<Setting>>,
+    tags: BTreeMap<Tag, Vec<Setting>>,
+}
+
+/// The output and tag
+/// whose settings a profile uses,
+/// if any.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ProfileKey {
+    output: Option<String>,
+    tag: Option<Tag>,
+}
+
+/// A layout generator configuration
+/// and the layouts it generated.
+#[derive(Debug)]
+struct Profile {
     inner: Arc<RawLayoutGen>,
-    cache: HashMap<Key, Arc<OnceCell<Vec<Rect>>>>,
+    cache: HashMap<Key, CacheEntry>,
+    disk_cache: Option<Arc<DiskCache>>,
+    /// Number of times each layout was rerolled,
+    /// added to the seed.
+    rerolls: HashMap<Key, u64>,
+    /// Index of the variant chosen
+    /// for each layout,
+    /// if not the best.
+    variants: HashMap<Key, usize>,
 }
 
+/// A layout,
+/// possibly still being generated.
+#[derive(Debug)]
+struct CacheEntry {
+    layout: Arc<Slot>,
+    /// Value of `LayoutGen::clock`
+    /// when this was last used.
+    last_used: AtomicU64,
+}
+
+/// A layout being generated
+/// and what is waiting for it.
+struct Slot {
+    /// Alternative layouts,
+    /// best first.
+    variants: OnceCell<Vec<Vec<Rect>>>,
+    /// Index of the variant used,
+    /// clamped to the number of variants.
+    selected: AtomicUsize,
+    state: Mutex<SlotState>,
+    cancel: CancellationToken,
+}
+
+struct SlotState {
+    priority: Priority,
+    /// Whether to call `LayoutGen::on_finish`
+    /// when the layout finishes.
+    notify: bool,
+    /// Called with the layout
+    /// when it finishes.
+    dependents: Vec<Dependent>,
+    /// Best layout found so far,
+    /// until finished.
+    intermediate: Option<Vec<Rect>>,
+}
+
+type Dependent = Box<dyn FnOnce(&[Rect]) + Send>;
+
+/// Called when a demanded layout finishes,
+/// or improves in anytime mode.
+#[derive(Clone)]
+struct OnFinish(Arc<dyn Fn() + Send + Sync>);
+
+/// Return an objective
+/// from the container,
+/// the number of windows,
+/// and the previous layout.
+pub type ObjectiveFactory = dyn Fn(Size, usize, &[Rect]) -> Box<dyn Objective> + Send + Sync;
+
+/// Objective added to built-in objectives
+/// by `LayoutGen::add_objective`.
+#[derive(Clone)]
+struct CustomObjective {
+    name: String,
+    weight: Weight,
+    factory: Arc<ObjectiveFactory>,
+}
+
+/// Abandons a layout when dropped,
+/// unless it finished.
+struct AbandonOnDrop(Arc<Slot>);
+
+/// Shared flag
+/// to stop generating a layout.
+#[derive(Clone, Debug, Default)]
+struct CancellationToken(Arc<AtomicBool>);
+
 #[derive(Clone, Debug)]
 struct RawLayoutGen {
     min_width: NonZeroUsize,
@@ -31,17 +187,93 @@ struct RawLayoutGen {
     weights: Weights,
     area_ratios: Vec<AreaRatio>,
     aspect_ratios: Vec<AspectRatio>,
+    seed: u64,
+    variants: NonZeroUsize,
+    optimizer: OptimizerConfig,
+    objectives: Vec<CustomObjective>,
 }
 
 type Key = (Size, usize);
 
+/// How soon a layout is needed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    /// The layout may be needed later.
+    Speculative,
+    /// River is waiting for the layout.
+    Demanded,
+}
+
+/// Number of layouts generated at once.
+/// Layouts share the cores,
+/// so more would only compete.
+/// The second lets a demanded layout start
+/// while a speculative layout is generated.
+const WORKERS: usize = 2;
+
+/// Minimum `layout_distance`
+/// between variants of a layout,
+/// so each looks different.
+const MIN_VARIANT_DISTANCE: f64 = 0.05;
+
+/// Minimum time between notifications
+/// of improving layouts,
+/// so River is not flooded with layout demands.
+const ANYTIME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Threads for generating layouts
+/// River is waiting for.
+///
+/// With `SPECULATIVE_POOL`,
+/// there is one thread per core,
+/// so layouts generated at once
+/// do not oversubscribe the CPU.
+static DEMANDED_POOL: Lazy<rayon::ThreadPool> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(pool_sizes(optimizer::cores()).0)
+        .thread_name(|i| format!("owm-demanded-{i}"))
+        .build()
+        .expect("thread pool should build")
+});
+
+/// Threads for generating layouts
+/// that may be needed later.
+/// They run at low priority,
+/// to avoid slowing layouts needed now
+/// and other programs.
+static SPECULATIVE_POOL: Lazy<rayon::ThreadPool> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(pool_sizes(optimizer::cores()).1)
+        .thread_name(|i| format!("owm-speculative-{i}"))
+        .start_handler(|_| lower_thread_priority())
+        .build()
+        .expect("thread pool should build")
+});
+
+/// Index of a bit in a River tags bitfield.
+type Tag = u32;
+
+/// Error returned when a setting would make the configuration invalid.
+#[derive(Clone, Copy, Debug, thiserror::Error, PartialEq)]
+pub enum InvalidConfigError {
+    /// Minimum width is above maximum width.
+    #[error("min width ({0}) must be <= max width ({1})")]
+    MinWidthAboveMax(NonZeroUsize, NonZeroUsize),
+    /// Minimum height is above maximum height.
+    #[error("min height ({0}) must be <= max height ({1})")]
+    MinHeightAboveMax(NonZeroUsize, NonZeroUsize),
+}
+
 pub enum Status<'a> {
     NotStarted,
     Started,
+    /// Best layout found so far,
+    /// in anytime mode.
+    Intermediate(Vec<Rect>),
     Finished(&'a [Rect]),
 }
 
-impl LayoutGen {
+impl LayoutConfig {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         min_width: NonZeroUsize,
@@ -52,9 +284,12 @@ impl LayoutGen {
         weights: Weights,
         area_ratios: Vec<AreaRatio>,
         aspect_ratios: Vec<AspectRatio>,
+        seed: u64,
+        variants: NonZeroUsize,
+        optimizer: OptimizerConfig,
     ) -> Self {
         Self {
-            inner: Arc::new(RawLayoutGen {
+            defaults: RawLayoutGen {
                 min_width,
                 min_height,
                 max_width,
@@ -63,86 +298,1388 @@ impl LayoutGen {
                 weights,
                 area_ratios,
                 aspect_ratios,
-            }),
-            cache: HashMap::new(),
+                seed,
+                variants,
+                optimizer,
+                objectives: Vec::new(),
+            },
+            overrides: Overrides::default(),
         }
     }
 
-    pub fn try_layout(&self, container: Size, count: usize) -> Status {
-        match self.cache.get(&(container, count)) {
-            Some(cache_cell) => match cache_cell.get() {
-                Some(layout) => Status::Finished(layout),
-                None => Status::Started,
-            },
-            None => Status::NotStarted,
+    /// Change settings for all outputs and tags
+    /// without their own values for them.
+    ///
+    /// Settings are validated together,
+    /// after all are applied.
+    pub fn set(
+        &mut self,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut defaults = self.defaults.clone();
+        for setting in settings {
+            defaults.set(setting);
         }
+        self.replace(defaults, self.overrides.clone())
     }
 
-    pub fn layout<F>(&mut self, container: Size, count: usize, callback: F)
-    where
-        F: FnOnce(&[Rect]) + Send + 'static,
-    {
-        self._layout(container, count, Box::new(callback))
+    /// Change settings for the output named `output`,
+    /// overriding the values set by `set`.
+    pub fn set_for_output(
+        &mut self,
+        output: &str,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut overrides = self.overrides.clone();
+        let output_overrides = overrides.outputs.entry(output.to_owned()).or_default();
+        for setting in settings {
+            override_setting(output_overrides, setting);
+        }
+        self.replace(self.defaults.clone(), overrides)
     }
 
-    // `Box` avoids infinite recusion during compilation.
-    #[allow(clippy::type_complexity)]
-    fn _layout(
+    /// Change settings for each tag in `tags`,
+    /// overriding the values set by `set` and `set_for_output`.
+    pub fn set_for_tags(
         &mut self,
+        tags: u32,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut overrides = self.overrides.clone();
+        for setting in settings {
+            for tag in iter_tags(tags) {
+                override_setting(overrides.tags.entry(tag).or_default(), setting.clone());
+            }
+        }
+        self.replace(self.defaults.clone(), overrides)
+    }
+
+    /// Use `defaults` and `overrides`
+    /// if they are valid together.
+    fn replace(
+        &mut self,
+        defaults: RawLayoutGen,
+        overrides: Overrides,
+    ) -> Result<(), InvalidConfigError> {
+        let config = Self {
+            defaults,
+            overrides,
+        };
+        config.profiles()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Return the configuration of each profile.
+    fn profiles(&self) -> Result<Vec<(ProfileKey, RawLayoutGen)>, InvalidConfigError> {
+        self.overrides
+            .profile_keys()
+            .map(|key| {
+                let mut config = self.defaults.clone();
+                for setting in self.overrides.settings(&key) {
+                    config.set(setting.clone());
+                }
+                config.validate().map(|_| (key, config))
+            })
+            .collect()
+    }
+}
+
+impl LayoutGen {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        min_width: NonZeroUsize,
+        min_height: NonZeroUsize,
+        max_width: Option<NonZeroUsize>,
+        max_height: Option<NonZeroUsize>,
+        overlap_borders_by: usize,
+        weights: Weights,
+        area_ratios: Vec<AreaRatio>,
+        aspect_ratios: Vec<AspectRatio>,
+        seed: u64,
+        variants: NonZeroUsize,
+        optimizer: OptimizerConfig,
+    ) -> Self {
+        Self::with_config(LayoutConfig::new(
+            min_width,
+            min_height,
+            max_width,
+            max_height,
+            overlap_borders_by,
+            weights,
+            area_ratios,
+            aspect_ratios,
+            seed,
+            variants,
+            optimizer,
+        ))
+    }
+
+    /// Return a layout generator
+    /// using the settings in `config`.
+    pub fn with_config(config: LayoutConfig) -> Self {
+        Self {
+            profiles: config
+                .profiles()
+                .expect("settings should be valid")
+                .into_iter()
+                .map(|(key, x)| (key, Profile::new(x, None)))
+                .collect(),
+            config,
+            disk_cache: None,
+            max_cached_layouts: None,
+            max_precomputed_count: 0,
+            anytime: false,
+            clock: AtomicU64::new(0),
+            scheduler: Scheduler::new(NonZeroUsize::new(WORKERS).unwrap()),
+            on_finish: OnFinish(Arc::new(|| {})),
+            demanded: HashMap::new(),
+        }
+    }
+
+    /// Call `f` when a layout started by `layout` finishes.
+    ///
+    /// `f` is called once per layout,
+    /// however many times it was demanded.
+    pub fn on_finish(&mut self, f: impl Fn() + Send + Sync + 'static) {
+        self.on_finish = OnFinish(Arc::new(f));
+    }
+
+    /// Keep at most `max` layouts in memory,
+    /// discarding least recently used layouts first.
+    ///
+    /// Layouts being generated,
+    /// and layouts they are generated from,
+    /// are kept regardless.
+    pub fn set_max_cached_layouts(&mut self, max: Option<NonZeroUsize>) {
+        self.max_cached_layouts = max;
+        self.evict();
+    }
+
+    /// Generate layouts for up to `count` windows
+    /// before they are needed,
+    /// using `warm` and `precompute`.
+    pub fn set_max_precomputed_count(&mut self, count: usize) {
+        self.max_precomputed_count = count;
+    }
+
+    /// Publish improving layouts
+    /// while they are generated,
+    /// calling `on_finish` for each,
+    /// at most every `ANYTIME_INTERVAL`.
+    ///
+    /// `try_layout` returns them
+    /// as `Status::Intermediate`.
+    pub fn set_anytime(&mut self, anytime: bool) {
+        self.anytime = anytime;
+    }
+
+    /// Load layouts from the file at `path`,
+    /// and save layouts to it
+    /// as they are generated.
+    pub fn use_cache_file(&mut self, path: &Path) -> io::Result<()> {
+        let disk_cache = Arc::new(DiskCache::open(path)?);
+        for profile in self.profiles.values_mut() {
+            profile.disk_cache = Some(Arc::clone(&disk_cache));
+            profile.load();
+        }
+        self.disk_cache = Some(disk_cache);
+        self.evict();
+        Ok(())
+    }
+
+    /// Change settings for all outputs and tags
+    /// without their own values for them,
+    /// discarding only cached layouts
+    /// the change may affect.
+    ///
+    /// Settings are validated together,
+    /// after all are applied.
+    pub fn set(
+        &mut self,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut config = self.config.clone();
+        config.set(settings)?;
+        self.reconfigure(config);
+        Ok(())
+    }
+
+    /// Change settings for the output named `output`,
+    /// overriding the values set by `set`.
+    pub fn set_for_output(
+        &mut self,
+        output: &str,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut config = self.config.clone();
+        config.set_for_output(output, settings)?;
+        self.reconfigure(config);
+        Ok(())
+    }
+
+    /// Remove settings specific to the output named `output`.
+    pub fn reset_output(&mut self, output: &str) {
+        let mut config = self.config.clone();
+        config.overrides.outputs.remove(output);
+        self.reconfigure(config);
+    }
+
+    /// Change settings for each tag in `tags`,
+    /// overriding the values set by `set` and `set_for_output`.
+    ///
+    /// Windows are laid out
+    /// using the settings of the lowest visible tag
+    /// with settings of its own.
+    pub fn set_for_tags(
+        &mut self,
+        tags: u32,
+        settings: impl IntoIterator<Item = Setting>,
+    ) -> Result<(), InvalidConfigError> {
+        let mut config = self.config.clone();
+        config.set_for_tags(tags, settings)?;
+        self.reconfigure(config);
+        Ok(())
+    }
+
+    /// Remove settings specific to each tag in `tags`.
+    pub fn reset_tags(&mut self, tags: u32) {
+        let mut config = self.config.clone();
+        config
+            .overrides
+            .tags
+            .retain(|tag, _| !contains_tag(tags, *tag));
+        self.reconfigure(config);
+    }
+
+    /// Add the objective returned by `factory`,
+    /// weighted by `weight`,
+    /// to the objectives of every layout,
+    /// discarding cached layouts.
+    ///
+    /// `name` identifies the objective
+    /// in the cache file,
+    /// so it should change
+    /// when the objective does.
+    pub fn add_objective(
+        &mut self,
+        name: &str,
+        weight: Weight,
+        factory: impl Fn(Size, usize, &[Rect]) -> Box<dyn Objective> + Send + Sync + 'static,
+    ) {
+        let mut config = self.config.clone();
+        config.defaults.objectives.push(CustomObjective {
+            name: name.to_owned(),
+            weight,
+            factory: Arc::new(factory),
+        });
+        self.reconfigure(config);
+    }
+
+    /// Use all settings from `config`,
+    /// discarding only cached layouts
+    /// the change may affect.
+    ///
+    /// Objectives added by `add_objective`
+    /// are kept.
+    pub fn set_from(&mut self, config: &LayoutConfig) {
+        let mut config = config.clone();
+        config.defaults.objectives = self.config.defaults.objectives.clone();
+        self.reconfigure(config);
+    }
+
+    /// Use `config`,
+    /// which must be valid.
+    fn reconfigure(&mut self, config: LayoutConfig) {
+        let configs = config
+            .profiles()
+            .expect("settings should be validated when set");
+        self.profiles.retain(|key, profile| {
+            let keep = config.overrides.contains(key);
+            if !keep {
+                for entry in profile.cache.values() {
+                    entry.layout.cancel.cancel();
+                }
+            }
+            keep
+        });
+        for (key, config) in configs {
+            match self.profiles.entry(key) {
+                Entry::Occupied(mut entry) => entry.get_mut().reconfigure(config),
+                Entry::Vacant(entry) => {
+                    entry.insert(Profile::new(config, self.disk_cache.clone()));
+                }
+            }
+        }
+        self.config = config;
+        self.evict();
+    }
+
+    /// Discard least recently used layouts
+    /// until at most `max_cached_layouts` remain,
+    /// if possible.
+    fn evict(&mut self) {
+        let Some(max) = self.max_cached_layouts else {
+            return;
+        };
+        let len = self.profiles.values().map(|x| x.cache.len()).sum::<usize>();
+        if len <= max.get() {
+            return;
+        }
+        let mut candidates = self
+            .profiles
+            .iter()
+            .flat_map(|(profile_key, profile)| {
+                profile
+                    .cache
+                    .iter()
+                    .filter(|(key, _)| !profile.is_pinned(**key))
+                    .map(move |(key, entry)| {
+                        (entry.last_used.load(Ordering::Relaxed), profile_key, *key)
+                    })
+            })
+            .collect::<Vec<_>>();
+        // Layouts with fewer windows
+        // may be needed to generate layouts with more.
+        candidates.sort_by_key(|(last_used, _, (_, count))| (*last_used, usize::MAX - count));
+        let evicted = candidates
+            .into_iter()
+            .take(len - max.get())
+            .map(|(_, profile_key, key)| (profile_key.clone(), key))
+            .collect::<Vec<_>>();
+        for (profile_key, key) in evicted {
+            self.profiles
+                .get_mut(&profile_key)
+                .expect("profile should exist")
+                .cache
+                .remove(&key);
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn profile_key(&self, output: &str, tags: u32) -> ProfileKey {
+        ProfileKey {
+            output: Some(output.to_owned())
+                .filter(|x| self.config.overrides.outputs.contains_key(x)),
+            tag: iter_tags(tags).find(|tag| self.config.overrides.tags.contains_key(tag)),
+        }
+    }
+
+    pub fn try_layout(&self, output: &str, tags: u32, container: Size, count: usize) -> Status {
+        self.profiles[&self.profile_key(output, tags)].try_layout(container, count, self.tick())
+    }
+
+    /// Start generating the layout
+    /// for `count` windows in `container`,
+    /// before any speculative layouts.
+    ///
+    /// `on_finish` is called
+    /// when it finishes,
+    /// or immediately if it already has.
+    ///
+    /// Layouts in progress
+    /// for the last container demanded for `output`
+    /// are cancelled and discarded
+    /// if `container` differs
+    /// and no other output uses it.
+    pub fn layout(&mut self, output: &str, tags: u32, container: Size, count: usize) {
+        if let Some((key, (prev_container, _))) = self.demanded.insert(
+            output.to_owned(),
+            (self.profile_key(output, tags), (container, count)),
+        ) {
+            if prev_container != container {
+                self.cancel_unless_demanded(&key, prev_container);
+            }
+        }
+        self.layout_with_priority(
+            self.profile_key(output, tags),
+            container,
+            count,
+            Priority::Demanded,
+        );
+    }
+
+    /// Forget the output named `output`,
+    /// after it is removed.
+    ///
+    /// Layouts in progress
+    /// for the last container demanded for `output`
+    /// are cancelled and discarded
+    /// if no other output uses it.
+    pub fn remove_output(&mut self, output: &str) {
+        if let Some((key, (container, _))) = self.demanded.remove(output) {
+            self.cancel_unless_demanded(&key, container);
+        }
+    }
+
+    /// Cancel and discard layouts in progress
+    /// for `container` in the profile for `key`,
+    /// unless they are the last demanded for an output.
+    fn cancel_unless_demanded(&mut self, key: &ProfileKey, container: Size) {
+        if !self
+            .demanded
+            .values()
+            .any(|(x, (y, _))| x == key && *y == container)
+        {
+            if let Some(profile) = self.profiles.get_mut(key) {
+                profile.cancel(container);
+            }
+        }
+    }
+
+    /// Generate the layout last demanded for `output` again,
+    /// with a different seed,
+    /// replacing it.
+    ///
+    /// Layouts for more windows in the same container
+    /// depend on it,
+    /// so they are generated again
+    /// at low priority.
+    pub fn reroll(&mut self, output: &str) {
+        let Some((key, (container, count))) = self.demanded.get(output).cloned() else {
+            return;
+        };
+        let Some(max_count) = self
+            .profiles
+            .get_mut(&key)
+            .and_then(|profile| profile.reroll(container, count))
+        else {
+            return;
+        };
+        self.layout_with_priority(key.clone(), container, count, Priority::Demanded);
+        if max_count > count {
+            self.layout_with_priority(key, container, max_count, Priority::Speculative);
+        }
+    }
+
+    /// Use the next variant
+    /// of the layout last demanded for `output`,
+    /// wrapping around.
+    ///
+    /// Layouts for more windows in the same container
+    /// are generated again from it,
+    /// at low priority.
+    pub fn next_variant(&mut self, output: &str) {
+        self.cycle_variant(output, true)
+    }
+
+    /// Use the previous variant
+    /// of the layout last demanded for `output`,
+    /// wrapping around.
+    ///
+    /// Layouts for more windows in the same container
+    /// are generated again from it,
+    /// at low priority.
+    pub fn prev_variant(&mut self, output: &str) {
+        self.cycle_variant(output, false)
+    }
+
+    fn cycle_variant(&mut self, output: &str, forward: bool) {
+        let Some((key, (container, count))) = self.demanded.get(output).cloned() else {
+            return;
+        };
+        let Some(max_count) = self
+            .profiles
+            .get_mut(&key)
+            .and_then(|profile| profile.cycle_variant(container, count, forward))
+        else {
+            return;
+        };
+        if max_count > count {
+            self.layout_with_priority(key, container, max_count, Priority::Speculative);
+        }
+    }
+
+    /// Start generating layouts
+    /// for up to `max_precomputed_count` windows
+    /// in `container`,
+    /// at low priority.
+    pub fn warm(&mut self, output: &str, tags: u32, container: Size) {
+        self.layout_with_priority(
+            self.profile_key(output, tags),
+            container,
+            self.max_precomputed_count,
+            Priority::Speculative,
+        );
+    }
+
+    /// Start generating the layout
+    /// for one more window than `count`
+    /// in `container`,
+    /// at low priority,
+    /// if it is for at most `max_precomputed_count` windows.
+    pub fn precompute(&mut self, output: &str, tags: u32, container: Size, count: usize) {
+        if count < self.max_precomputed_count {
+            self.layout_with_priority(
+                self.profile_key(output, tags),
+                container,
+                count + 1,
+                Priority::Speculative,
+            );
+        }
+    }
+
+    /// Start generating the layout
+    /// for `count` windows in `container`
+    /// with the profile for `key`,
+    /// or raise its priority,
+    /// and return it.
+    fn layout_with_priority(
+        &mut self,
+        key: ProfileKey,
         container: Size,
         count: usize,
-        callback: Box<dyn FnOnce(&[Rect]) + Send + 'static>,
-    ) {
-        let key = (container, count);
-        if count == 0 {
-            return (callback)(
+        priority: Priority,
+    ) -> Arc<Slot> {
+        let now = self.tick();
+        let notify = priority == Priority::Demanded;
+        let (slot, in_progress) = self
+            .profiles
+            .get_mut(&key)
+            .expect("profile should exist for every combination of overrides")
+            .layout(
+                container,
+                count,
+                now,
+                priority,
+                notify,
+                self.anytime,
+                &self.scheduler.spawner(),
+                &self.on_finish,
+            );
+        if notify && !in_progress {
+            (self.on_finish.0)();
+        }
+        self.evict();
+        slot
+    }
+
+    /// Return the layout
+    /// for `count` windows in `container`,
+    /// generating it if needed,
+    /// and blocking until it finishes,
+    /// or `None` if it is cancelled
+    /// or fails.
+    ///
+    /// Unlike `layout`,
+    /// this does not cancel layouts
+    /// for other containers.
+    pub fn generate(
+        &mut self,
+        output: &str,
+        tags: u32,
+        container: Size,
+        count: usize,
+    ) -> Option<Vec<Rect>> {
+        let key = self.profile_key(output, tags);
+        let slot = self.layout_with_priority(key, container, count, Priority::Demanded);
+        let (sender, receiver) = mpsc::channel();
+        slot.then(move |layout| {
+            let _ = sender.send(layout.to_vec());
+        });
+        // The sender is dropped
+        // if the layout is abandoned.
+        receiver.recv().ok()
+    }
+
+    /// Return how each objective scores
+    /// the finished layout
+    /// for `count` windows in `container`,
+    /// or `None` if it is not finished
+    /// or has no windows.
+    ///
+    /// Scores are for the layout as shown,
+    /// after overlapping borders.
+    pub fn explain(
+        &self,
+        output: &str,
+        tags: u32,
+        container: Size,
+        count: usize,
+    ) -> Option<Vec<ObjectiveScore>> {
+        self.profiles[&self.profile_key(output, tags)].explain(container, count)
+    }
+
+    /// Return a layout available immediately,
+    /// to show until `layout` finishes.
+    ///
+    /// This is the cached layout
+    /// for the same number of windows
+    /// and the most similar container,
+    /// rescaled,
+    /// if any,
+    /// or a main and stack layout.
+    pub fn fallback_layout(
+        &self,
+        output: &str,
+        tags: u32,
+        container: Size,
+        count: usize,
+    ) -> Vec<Rect> {
+        self.profiles[&self.profile_key(output, tags)].fallback_layout(container, count)
+    }
+}
+
+impl Overrides {
+    fn profile_keys(&self) -> impl Iterator<Item = ProfileKey> + '_ {
+        once(None)
+            .chain(self.outputs.keys().cloned().map(Some))
+            .flat_map(move |output| {
+                once(None)
+                    .chain(self.tags.keys().copied().map(Some))
+                    .map(move |tag| ProfileKey {
+                        output: output.clone(),
+                        tag,
+                    })
+            })
+    }
+
+    fn contains(&self, key: &ProfileKey) -> bool {
+        key.output.iter().all(|x| self.outputs.contains_key(x))
+            && key.tag.iter().all(|x| self.tags.contains_key(x))
+    }
+
+    /// Return settings for `key`
+    /// in order of increasing precedence.
+    fn settings<'a>(&'a self, key: &'a ProfileKey) -> impl Iterator<Item = &'a Setting> {
+        key.output
+            .iter()
+            .flat_map(|x| &self.outputs[x])
+            .chain(key.tag.iter().flat_map(|x| &self.tags[x]))
+    }
+}
+
+fn override_setting(settings: &mut Vec<Setting>, setting: Setting) {
+    settings.retain(|x| discriminant(x) != discriminant(&setting));
+    settings.push(setting);
+}
+
+fn iter_tags(tags: u32) -> impl Iterator<Item = Tag> {
+    (0..u32::BITS).filter(move |tag| contains_tag(tags, *tag))
+}
+
+fn contains_tag(tags: u32, tag: Tag) -> bool {
+    tags & (1 << tag) != 0
+}
+
+impl Profile {
+    fn new(gen: RawLayoutGen, disk_cache: Option<Arc<DiskCache>>) -> Self {
+        let mut profile = Self {
+            inner: Arc::new(gen),
+            cache: HashMap::new(),
+            disk_cache,
+            rerolls: HashMap::new(),
+            variants: HashMap::new(),
+        };
+        profile.load();
+        profile
+    }
+
+    /// Add layouts saved to disk
+    /// for the current configuration.
+    fn load(&mut self) {
+        if let Some(disk_cache) = &self.disk_cache {
+            // Saved layouts are only an optimization.
+            for (key, layout) in disk_cache
+                .layouts(hash_config(&*self.inner))
+                .unwrap_or_default()
+            {
                 self.cache
                     .entry(key)
-                    .or_insert(Arc::new(OnceCell::new()))
-                    .get_or_init(Vec::new),
-            );
+                    .or_insert_with(|| CacheEntry::new(Slot::finished(layout), 0));
+            }
+        }
+    }
+
+    /// Use a new configuration,
+    /// discarding only cached layouts
+    /// the change may affect.
+    fn reconfigure(&mut self, gen: RawLayoutGen) {
+        if let Some(count) = self.inner.first_differing_count(&gen) {
+            self.cache.retain(|(_, x), entry| {
+                let keep = *x < count;
+                if !keep {
+                    // Layouts in progress use the old configuration,
+                    // so their results would be wrong.
+                    entry.layout.cancel.cancel();
+                }
+                keep
+            });
+            self.rerolls.retain(|(_, x), _| *x < count);
+            self.variants.retain(|(_, x), _| *x < count);
+            self.inner = Arc::new(gen);
+            self.load();
+        }
+    }
+
+    fn try_layout(&self, container: Size, count: usize, now: u64) -> Status {
+        match self.cache.get(&(container, count)) {
+            Some(entry) => {
+                entry.last_used.store(now, Ordering::Relaxed);
+                match entry.layout.get() {
+                    Some(layout) => Status::Finished(layout),
+                    None => entry
+                        .layout
+                        .intermediate()
+                        .map_or(Status::Started, Status::Intermediate),
+                }
+            }
+            None => Status::NotStarted,
+        }
+    }
+
+    fn explain(&self, container: Size, count: usize) -> Option<Vec<ObjectiveScore>> {
+        let layout = |count| self.cache.get(&(container, count))?.layout.get();
+        let prev_layout = layout(count.checked_sub(1)?)?;
+        Some(
+            self.inner
+                .problem(container, prev_layout.clone())
+                .evaluate_detailed(layout(count)?),
+        )
+    }
+
+    /// Stop generating layouts for `container`,
+    /// discarding them.
+    fn cancel(&mut self, container: Size) {
+        self.cache.retain(|(x, _), entry| {
+            let in_progress = *x == container && entry.layout.get().is_none();
+            if in_progress {
+                entry.layout.cancel.cancel();
+            }
+            !in_progress
+        });
+    }
+
+    /// Discard the layout
+    /// for `count` windows in `container`
+    /// and layouts generated from it,
+    /// and use a different seed for it.
+    ///
+    /// Return the most windows
+    /// of a discarded layout,
+    /// or `None` if there is nothing to reroll.
+    fn reroll(&mut self, container: Size, count: usize) -> Option<usize> {
+        // There is only one layout for no windows.
+        if count == 0 {
+            return None;
         }
-        match self.cache.entry(key) {
+        *self.rerolls.entry((container, count)).or_default() += 1;
+        Some(self.discard_from(container, count).unwrap_or(count))
+    }
+
+    /// Select another variant
+    /// of the layout for `count` windows in `container`,
+    /// discarding layouts generated from the previous variant.
+    ///
+    /// Return the most windows
+    /// of a discarded layout,
+    /// or `count` if none were discarded,
+    /// or `None` if there is no other variant.
+    fn cycle_variant(&mut self, container: Size, count: usize, forward: bool) -> Option<usize> {
+        let slot = Arc::clone(&self.cache.get(&(container, count))?.layout);
+        let selected = slot.cycle(forward)?;
+        self.variants.insert((container, count), selected);
+        if let (Some(disk_cache), Some(layout)) = (&self.disk_cache, slot.get()) {
+            // Later lines replace earlier lines,
+            // so the choice is kept after restarting.
+            let _ = disk_cache.insert(hash_config(&*self.inner), container, layout);
+        }
+        Some(self.discard_from(container, count + 1).unwrap_or(count))
+    }
+
+    /// Discard layouts
+    /// for at least `count` windows in `container`,
+    /// and their chosen variants,
+    /// cancelling those in progress,
+    /// and return the most windows
+    /// of a discarded layout.
+    fn discard_from(&mut self, container: Size, count: usize) -> Option<usize> {
+        // New layouts have new variants,
+        // so choices for old layouts would be wrong.
+        self.variants
+            .retain(|(x, y), _| !(*x == container && *y >= count));
+        let mut max_count = None;
+        self.cache.retain(|(x, y), entry| {
+            let discard = *x == container && *y >= count;
+            if discard {
+                entry.layout.cancel.cancel();
+                max_count = max_count.max(Some(*y));
+            }
+            !discard
+        });
+        max_count
+    }
+
+    /// Return whether the layout for `key`
+    /// is being generated,
+    /// or is needed to generate another layout.
+    fn is_pinned(&self, (container, count): Key) -> bool {
+        [count, count + 1].into_iter().any(|count| {
+            self.cache
+                .get(&(container, count))
+                .is_some_and(|entry| entry.layout.get().is_none())
+        })
+    }
+
+    fn fallback_layout(&self, container: Size, count: usize) -> Vec<Rect> {
+        self.nearest_layout(container, count)
+            .unwrap_or_else(|| self.inner.fallback_layout(container, count))
+    }
+
+    /// Return the finished layout
+    /// for `count` windows
+    /// and the most similar container,
+    /// rescaled to `container`,
+    /// if any.
+    fn nearest_layout(&self, container: Size, count: usize) -> Option<Vec<Rect>> {
+        self.cache
+            .iter()
+            .filter(|((_, x), _)| *x == count)
+            .filter_map(|((size, _), entry)| entry.layout.get().map(|layout| (*size, layout)))
+            .min_by_key(|(size, _)| size.diff(container))
+            .map(|(size, layout)| rescale(size, container, layout))
+    }
+
+    /// Start generating the layout
+    /// for `count` windows in `container`,
+    /// and layouts it depends on,
+    /// or raise their priority
+    /// if already started.
+    ///
+    /// Return the layout
+    /// and whether it is in progress.
+    #[allow(clippy::too_many_arguments)]
+    fn layout(
+        &mut self,
+        container: Size,
+        count: usize,
+        now: u64,
+        priority: Priority,
+        notify: bool,
+        anytime: bool,
+        spawner: &Spawner,
+        on_finish: &OnFinish,
+    ) -> (Arc<Slot>, bool) {
+        match self.cache.entry((container, count)) {
+            Entry::Occupied(entry) => {
+                entry.get().last_used.store(now, Ordering::Relaxed);
+                let slot = Arc::clone(&entry.get().layout);
+                let in_progress = slot.raise(priority, notify);
+                if in_progress && count > 0 {
+                    // Layouts this depends on
+                    // are needed as soon.
+                    self.layout(
+                        container,
+                        count - 1,
+                        now,
+                        priority,
+                        false,
+                        anytime,
+                        spawner,
+                        on_finish,
+                    );
+                }
+                (slot, in_progress)
+            }
+            Entry::Vacant(entry) if count == 0 => {
+                let entry = entry.insert(CacheEntry::new(Slot::finished(Vec::new()), now));
+                (Arc::clone(&entry.layout), false)
+            }
             Entry::Vacant(entry) => {
-                let cache_cell = Arc::clone(entry.insert(Arc::new(OnceCell::new())));
-                let gen = Arc::clone(&self.inner);
+                let slot = Arc::clone(
+                    &entry
+                        .insert(CacheEntry::new(
+                            Slot::new(
+                                priority,
+                                notify,
+                                self.variants.get(&(container, count)).copied().unwrap_or(0),
+                            ),
+                            now,
+                        ))
+                        .layout,
+                );
                 self.layout(
                     container,
                     count - 1,
-                    Box::new(move |prev_layout: &[Rect]| {
-                        let prev_layout = prev_layout.to_vec();
-                        thread::spawn(move || {
-                            let layout = gen.layout(container, prev_layout);
-                            let layout = cache_cell
-                                .try_insert(layout)
-                                .expect("cell should be unset for {key:?}");
-                            (callback)(layout)
-                        });
-                    }),
+                    now,
+                    priority,
+                    false,
+                    anytime,
+                    spawner,
+                    on_finish,
                 );
+                let prev_slot = Arc::clone(&self.cache[&(container, count - 1)].layout);
+                let nearby = self.nearest_layout(container, count);
+                let gen = Arc::clone(&self.inner);
+                let seed = gen
+                    .seed
+                    .wrapping_add(self.rerolls.get(&(container, count)).copied().unwrap_or(0));
+                let disk_cache = self.disk_cache.clone();
+                let spawner = spawner.clone();
+                let on_finish = on_finish.clone();
+                // If the previous layout is abandoned,
+                // or this job is cancelled or panics,
+                // this layout will never finish.
+                let abandon = AbandonOnDrop(Arc::clone(&slot));
+                let result = Arc::clone(&slot);
+                prev_slot.then(move |prev_layout| {
+                    let prev_layout = prev_layout.to_vec();
+                    let priority_slot = Arc::clone(&slot);
+                    spawner.spawn(
+                        move || priority_slot.priority(),
+                        move |priority| {
+                            let _abandon = abandon;
+                            let last_notified = Mutex::new(None::<Instant>);
+                            let on_improvement = |layout| {
+                                let mut last_notified = last_notified.lock().unwrap();
+                                if slot.improve(layout)
+                                    && !last_notified
+                                        .is_some_and(|x| x.elapsed() < ANYTIME_INTERVAL)
+                                {
+                                    *last_notified = Some(Instant::now());
+                                    (on_finish.0)();
+                                }
+                            };
+                            let generate = || {
+                                gen.layout(
+                                    container,
+                                    prev_layout,
+                                    nearby,
+                                    seed,
+                                    &slot.cancel,
+                                    anytime.then_some(&on_improvement),
+                                )
+                            };
+                            let Some(variants) = (match priority {
+                                Priority::Speculative => SPECULATIVE_POOL.install(generate),
+                                Priority::Demanded => DEMANDED_POOL.install(generate),
+                            }) else {
+                                return;
+                            };
+                            let notify = slot.finish(variants);
+                            if let Some(disk_cache) = disk_cache {
+                                // Saving layouts is only an optimization.
+                                let _ = disk_cache.insert(
+                                    hash_config(&*gen),
+                                    container,
+                                    slot.get().expect("layout should be finished"),
+                                );
+                            }
+                            if notify {
+                                (on_finish.0)();
+                            }
+                        },
+                    );
+                });
+                (result, true)
             }
-            Entry::Occupied(entry) => {
-                let cache_cell = entry.get();
-                if let Some(layout) = cache_cell.get() {
-                    (callback)(layout)
-                } else {
-                    let cache_cell = Arc::clone(cache_cell);
-                    thread::spawn(move || (callback)(cache_cell.wait()));
-                }
+        }
+    }
+}
+
+impl CacheEntry {
+    fn new(layout: Slot, now: u64) -> Self {
+        Self {
+            layout: Arc::new(layout),
+            last_used: AtomicU64::new(now),
+        }
+    }
+}
+
+impl Slot {
+    fn new(priority: Priority, notify: bool, selected: usize) -> Self {
+        Self {
+            variants: OnceCell::new(),
+            selected: AtomicUsize::new(selected),
+            state: Mutex::new(SlotState {
+                priority,
+                notify,
+                dependents: Vec::new(),
+                intermediate: None,
+            }),
+            cancel: CancellationToken::default(),
+        }
+    }
+
+    fn finished(layout: Vec<Rect>) -> Self {
+        let slot = Self::new(Priority::Speculative, false, 0);
+        slot.variants.set(vec![layout]).unwrap();
+        slot
+    }
+
+    /// Return the selected variant,
+    /// if finished.
+    fn get(&self) -> Option<&Vec<Rect>> {
+        self.variants.get().map(|variants| {
+            &variants[self
+                .selected
+                .load(Ordering::Relaxed)
+                .min(variants.len() - 1)]
+        })
+    }
+
+    /// Select the next variant,
+    /// or the previous if `!forward`,
+    /// wrapping around,
+    /// and return its index,
+    /// or `None` if there is no other variant.
+    fn cycle(&self, forward: bool) -> Option<usize> {
+        let len = self.variants.get()?.len();
+        if len < 2 {
+            return None;
+        }
+        let selected = self.selected.load(Ordering::Relaxed).min(len - 1);
+        let selected = if forward {
+            (selected + 1) % len
+        } else {
+            (selected + len - 1) % len
+        };
+        self.selected.store(selected, Ordering::Relaxed);
+        Some(selected)
+    }
+
+    fn priority(&self) -> Priority {
+        self.state.lock().unwrap().priority
+    }
+
+    /// Raise priority to at least `priority`,
+    /// and notify when finished if `notify`,
+    /// returning whether the layout is in progress.
+    fn raise(&self, priority: Priority, notify: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        // The layout is only set
+        // while the state is locked.
+        if self.variants.get().is_some() {
+            return false;
+        }
+        state.priority = state.priority.max(priority);
+        state.notify |= notify;
+        true
+    }
+
+    /// Return the best layout found so far,
+    /// if unfinished and any was found.
+    fn intermediate(&self) -> Option<Vec<Rect>> {
+        self.state.lock().unwrap().intermediate.clone()
+    }
+
+    /// Keep `layout` as the best found so far,
+    /// if unfinished,
+    /// returning whether to notify.
+    fn improve(&self, layout: Vec<Rect>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if self.variants.get().is_some() {
+            return false;
+        }
+        state.intermediate = Some(layout);
+        state.notify
+    }
+
+    /// Stop generating the layout,
+    /// if unfinished,
+    /// and drop what is waiting for it,
+    /// because it will never finish.
+    fn abandon(&self) {
+        let mut state = self.state.lock().unwrap();
+        if self.variants.get().is_none() {
+            self.cancel.cancel();
+            let dependents = std::mem::take(&mut state.dependents);
+            // Dependents may abandon other layouts.
+            drop(state);
+            drop(dependents);
+        }
+    }
+
+    /// Call `f` with the layout
+    /// when it finishes,
+    /// or immediately if it has.
+    fn then(&self, f: impl FnOnce(&[Rect]) + Send + 'static) {
+        let mut state = self.state.lock().unwrap();
+        match self.get() {
+            Some(layout) => {
+                drop(state);
+                f(layout)
             }
+            None => state.dependents.push(Box::new(f)),
+        }
+    }
+
+    /// Set the variants
+    /// and call what is waiting for the selected one,
+    /// returning whether to notify.
+    fn finish(&self, variants: Vec<Vec<Rect>>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.variants
+            .set(variants)
+            .expect("layout should only finish once");
+        let dependents = std::mem::take(&mut state.dependents);
+        state.intermediate = None;
+        let notify = state.notify;
+        drop(state);
+        let layout = self.get().unwrap();
+        for f in dependents {
+            f(layout)
         }
+        notify
+    }
+}
+
+impl CancellationToken {
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for AbandonOnDrop {
+    fn drop(&mut self) {
+        self.0.abandon();
+    }
+}
+
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("variants", &self.variants)
+            .field("selected", &self.selected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for CustomObjective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The configuration hash
+        // identifies objectives by name and weight.
+        f.debug_struct("CustomObjective")
+            .field("name", &self.name)
+            .field("weight", &self.weight)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for CustomObjective {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.weight == other.weight
+            && Arc::ptr_eq(&self.factory, &other.factory)
+    }
+}
+
+impl fmt::Debug for OnFinish {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnFinish").finish_non_exhaustive()
     }
 }
 
 impl RawLayoutGen {
-    fn layout(&self, container: Size, prev_layout: Vec<Rect>) -> Vec<Rect> {
-        let count = prev_layout.len() + 1;
-        let max_size = Size::new(
+    fn set(&mut self, setting: Setting) {
+        match setting {
+            Setting::MinWidth(x) => self.min_width = x,
+            Setting::MinHeight(x) => self.min_height = x,
+            Setting::MaxWidth(x) => self.max_width = x,
+            Setting::MaxHeight(x) => self.max_height = x,
+            Setting::OverlapBordersBy(x) => self.overlap_borders_by = x,
+            Setting::GapsWeight(x) => self.weights.gaps_weight = x,
+            Setting::OverlapWeight(x) => self.weights.overlap_weight = x,
+            Setting::AreaRatios(x) => self.area_ratios = x,
+            Setting::AreaRatiosWeight(x) => self.weights.area_ratios_weight = x,
+            Setting::AspectRatios(x) => self.aspect_ratios = x,
+            Setting::AspectRatiosWeight(x) => self.weights.aspect_ratios_weight = x,
+            Setting::AdjacentCloseWeight(x) => self.weights.adjacent_close_weight = x,
+            Setting::ReadingOrderWeight(x) => self.weights.reading_order_weight = x,
+            Setting::CenterMainWeight(x) => self.weights.center_main_weight = x,
+            Setting::ConsistencyWeight(x) => self.weights.consistency_weight = x,
+            Setting::Seed(x) => self.seed = x,
+            Setting::Variants(x) => self.variants = x,
+            Setting::Algorithm(x) => self.optimizer.algorithm = x,
+            Setting::SamplesPerCore(x) => self.optimizer.samples_per_core = x,
+            Setting::AdjustRate(x) => self.optimizer.adjust_rate = x,
+            Setting::MutationChance(x) => self.optimizer.mutation_chance = x,
+            Setting::MutationAdjustRate(x) => self.optimizer.mutation_adjust_rate = x,
+            Setting::ConvergedThreshold(x) => self.optimizer.converged_threshold = x,
+            Setting::TimeBudget(x) => self.optimizer.time_budget = x,
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidConfigError> {
+        if let Some(max_width) = self.max_width {
+            if self.min_width > max_width {
+                return Err(InvalidConfigError::MinWidthAboveMax(
+                    self.min_width,
+                    max_width,
+                ));
+            }
+        }
+        if let Some(max_height) = self.max_height {
+            if self.min_height > max_height {
+                return Err(InvalidConfigError::MinHeightAboveMax(
+                    self.min_height,
+                    max_height,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the smallest number of windows
+    /// that may be laid out differently
+    /// by `self` and `other`,
+    /// or `None` if they always generate the same layouts.
+    fn first_differing_count(&self, other: &Self) -> Option<usize> {
+        // Objectives comparing pairs of windows,
+        // or comparing to the previous layout,
+        // cannot affect a single window.
+        [
+            (self.min_width != other.min_width, 1),
+            (self.min_height != other.min_height, 1),
+            (self.max_width != other.max_width, 1),
+            (self.max_height != other.max_height, 1),
+            (self.overlap_borders_by != other.overlap_borders_by, 1),
+            (self.weights.gaps_weight != other.weights.gaps_weight, 1),
+            (
+                self.weights.overlap_weight != other.weights.overlap_weight,
+                2,
+            ),
+            (self.area_ratios != other.area_ratios, 2),
+            (
+                self.weights.area_ratios_weight != other.weights.area_ratios_weight,
+                2,
+            ),
+            (self.aspect_ratios != other.aspect_ratios, 1),
+            (
+                self.weights.aspect_ratios_weight != other.weights.aspect_ratios_weight,
+                1,
+            ),
+            (
+                self.weights.adjacent_close_weight != other.weights.adjacent_close_weight,
+                2,
+            ),
+            (
+                self.weights.reading_order_weight != other.weights.reading_order_weight,
+                2,
+            ),
+            (
+                self.weights.center_main_weight != other.weights.center_main_weight,
+                1,
+            ),
+            (
+                self.weights.consistency_weight != other.weights.consistency_weight,
+                2,
+            ),
+            (self.seed != other.seed, 1),
+            (self.variants != other.variants, 1),
+            (self.optimizer != other.optimizer, 1),
+            (self.objectives != other.objectives, 1),
+        ]
+        .into_iter()
+        .filter(|(changed, _)| *changed)
+        .map(|(_, count)| count)
+        .min()
+    }
+
+    fn max_size(&self, container: Size) -> Size {
+        Size::new(
             self.max_width
                 .map_or(container.width, |x| x.min(container.width)),
             self.max_height
                 .map_or(container.height, |x| x.min(container.height)),
+        )
+    }
+
+    /// Return the problem
+    /// of laying out one more window than `prev_layout`.
+    fn problem(&self, container: Size, prev_layout: Vec<Rect>) -> Problem {
+        let count = prev_layout.len() + 1;
+        let custom = self
+            .objectives
+            .iter()
+            .map(|x| (x.weight, (x.factory)(container, count, &prev_layout)))
+            .collect::<Vec<_>>();
+        let mut problem = Problem::new(
+            self.weights,
+            self.area_ratios.clone(),
+            self.aspect_ratios.clone(),
+            self.max_size(container),
+            container,
+            prev_layout,
         );
+        for (weight, objective) in custom {
+            problem.push(weight, objective);
+        }
+        problem
+    }
+
+    /// Return a main and stack layout,
+    /// with windows shrunk to the maximum size
+    /// and centered in their place.
+    fn fallback_layout(&self, container: Size, count: usize) -> Vec<Rect> {
+        let mut rects = self.fit_max_size(container, main_stack(container, count));
+        if self.overlap_borders_by > 0 {
+            overlap_borders(self.overlap_borders_by, container, &mut rects);
+        }
+        rects
+    }
+
+    /// Return `rects` shrunk to the maximum size
+    /// and centered in their place.
+    fn fit_max_size(&self, container: Size, rects: Vec<Rect>) -> Vec<Rect> {
+        let max_size = self.max_size(container);
+        rects
+            .into_iter()
+            .map(|rect| {
+                let size = Size::new(
+                    rect.width().min(max_size.width),
+                    rect.height().min(max_size.height),
+                );
+                Rect::new(
+                    rect.x() + (rect.width().get() - size.width.get()) / 2,
+                    rect.y() + (rect.height().get() - size.height.get()) / 2,
+                    size.width,
+                    size.height,
+                )
+            })
+            .collect()
+    }
+
+    /// Return up to `variants` distinct layouts,
+    /// best first,
+    /// for one more window than `prev_layout`,
+    /// or `None` if cancelled.
+    /// The best is no worse
+    /// than the best classic layout.
+    ///
+    /// The search starts near `prev_layout`
+    /// with its largest window split,
+    /// and near `nearby`,
+    /// the layout for a similar container,
+    /// if any.
+    ///
+    /// `seed` replaces the configured seed,
+    /// so a layout can be generated again
+    /// with different results.
+    ///
+    /// `on_improvement` is called
+    /// with improving layouts for the first variant,
+    /// if given.
+    /// The time budget is split evenly between variants.
+    fn layout(
+        &self,
+        container: Size,
+        prev_layout: Vec<Rect>,
+        nearby: Option<Vec<Rect>>,
+        seed: u64,
+        cancel: &CancellationToken,
+        on_improvement: Option<&(dyn Fn(Vec<Rect>) + Sync)>,
+    ) -> Option<Vec<Vec<Rect>>> {
+        let start = Instant::now();
+        let count = prev_layout.len() + 1;
+        let max_size = self.max_size(container);
         let decoder = Decoder::new(
             Size::new(
                 self.min_width.min(container.width),
@@ -152,51 +1689,808 @@ impl RawLayoutGen {
             container,
             count,
         );
-        let problem = Problem::new(
-            self.weights,
-            self.area_ratios.clone(),
-            self.aspect_ratios.clone(),
-            max_size,
-            container,
-            prev_layout,
-        );
-        let mut rects = decoder
-            .decode1(
-                UntilConvergedConfig {
-                    threshold: ProbabilityThreshold::new(Probability::new(0.9).unwrap()).unwrap(),
+        let hints = once(split_largest(container, &prev_layout))
+            .chain(nearby)
+            .collect::<Vec<_>>();
+        let problem = self.problem(container, prev_layout);
+        // Each run continues the same random stream,
+        // so the first is the same
+        // however many variants are generated.
+        let mut rng = SplitMix64::seed_from_u64(seed);
+        let mut candidates = Vec::<(f64, Vec<Rect>)>::new();
+        let optimizer = optimizer::from_config(self.optimizer);
+        let is_cancelled = || cancel.is_cancelled();
+        let on_improvement = on_improvement.map(|f| {
+            move |mut rects: Vec<Rect>| {
+                if self.overlap_borders_by > 0 {
+                    overlap_borders(self.overlap_borders_by, container, &mut rects);
                 }
-                .argmin(
-                    &mut Config {
-                        num_samples: NumSamples::new(
-                            500 * std::thread::available_parallelism().map_or(1, |x| x.into()),
-                        )
-                        .unwrap(),
-                        adjust_rate: AdjustRate::new(0.1).unwrap(),
-                        mutation_chance: MutationChance::new(0.0).unwrap(),
-                        mutation_adjust_rate: MutationAdjustRate::new(0.05).unwrap(),
+                f(rects)
+            }
+        });
+        let variants = self.variants.get();
+        for i in 0..variants {
+            let control = Control::new(&is_cancelled).with_deadline(
+                self.optimizer
+                    .time_budget
+                    .map(|x| start + x.mul_f64((i + 1) as f64 / variants as f64)),
+            );
+            let control = match &on_improvement {
+                Some(f) if i == 0 => control.with_on_improvement(f),
+                _ => control,
+            };
+            let rects = optimizer.optimize(&decoder, &problem, &hints, &mut rng, &control)?;
+            // Bits cannot represent every position and size.
+            let rects = optimizer::polish(&decoder, &problem, rects, &control)?;
+            candidates.push((problem.evaluate(&rects), rects));
+        }
+        // The optimizer may miss simple layouts,
+        // so the result is never worse
+        // than the best classic layout.
+        if let Some(template) = templates::all(container, count)
+            .into_iter()
+            .map(|rects| self.fit_max_size(container, rects))
+            .map(|rects| (problem.evaluate(&rects), rects))
+            .min_by(|(x, _), (y, _)| x.total_cmp(y))
+        {
+            candidates.push(template);
+        }
+        candidates.sort_by(|(x, _), (y, _)| x.total_cmp(y));
+        // Runs often converge to the same layout,
+        // or nearly so,
+        // so variants too similar to a better one are skipped.
+        let mut distinct = Vec::<Vec<Rect>>::new();
+        for (_, rects) in candidates {
+            if distinct.len() < variants
+                && distinct
+                    .iter()
+                    .all(|x| layout_distance(container, x, &rects) >= MIN_VARIANT_DISTANCE)
+            {
+                distinct.push(rects);
+            }
+        }
+        Some(
+            distinct
+                .into_iter()
+                .map(|mut rects| {
+                    if self.overlap_borders_by > 0 {
+                        overlap_borders(self.overlap_borders_by, container, &mut rects);
                     }
-                    .start_using(
-                        decoder.bits(),
-                        |points| {
-                            (0..points.nrows())
-                                .into_par_iter()
-                                .map(|i| {
-                                    problem.evaluate(
-                                        decoder.decode1(points.row(i)).as_slice().unwrap(),
-                                    )
-                                })
-                                .collect::<Vec<_>>()
-                                .into()
-                        },
-                        &mut SplitMix64::seed_from_u64(0),
-                    ),
-                )
-                .view(),
+                    rects
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Return the largest difference
+/// between corresponding window edges
+/// of `a` and `b`,
+/// as a fraction of the size of `container`.
+fn layout_distance(container: Size, a: &[Rect], b: &[Rect]) -> f64 {
+    let width = container.width.get() as f64;
+    let height = container.height.get() as f64;
+    a.iter()
+        .zip(b)
+        .flat_map(|(a, b)| {
+            [
+                a.left().abs_diff(b.left()) as f64 / width,
+                a.right().abs_diff(b.right()) as f64 / width,
+                a.top().abs_diff(b.top()) as f64 / height,
+                a.bottom().abs_diff(b.bottom()) as f64 / height,
+            ]
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Return the number of threads
+/// for `DEMANDED_POOL` and `SPECULATIVE_POOL`
+/// given `cores`.
+///
+/// Speculative layouts use
+/// at most all workers but one,
+/// so they get the share of those workers,
+/// and demanded layouts share the rest.
+fn pool_sizes(cores: usize) -> (usize, usize) {
+    let speculative = (cores * (WORKERS - 1) / WORKERS).max(1);
+    (cores.saturating_sub(speculative).max(1), speculative)
+}
+
+fn lower_thread_priority() {
+    // Lower priority is only a courtesy,
+    // so errors are ignored.
+    // On Linux,
+    // this only affects the calling thread.
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+    }
+}
+
+/// Scale a layout from one container to another.
+fn rescale(from: Size, to: Size, layout: &[Rect]) -> Vec<Rect> {
+    let scale = |x: usize, from: NonZeroUsize, to: NonZeroUsize| x * to.get() / from.get();
+    layout
+        .iter()
+        .map(|rect| {
+            let left = scale(rect.left(), from.width, to.width).min(to.width.get() - 1);
+            let top = scale(rect.top(), from.height, to.height).min(to.height.get() - 1);
+            Rect::new(
+                left,
+                top,
+                NonZeroUsize::new(scale(rect.right(), from.width, to.width).saturating_sub(left))
+                    .unwrap_or(NonZeroUsize::MIN),
+                NonZeroUsize::new(scale(rect.bottom(), from.height, to.height).saturating_sub(top))
+                    .unwrap_or(NonZeroUsize::MIN),
             )
-            .into_raw_vec();
-        if self.overlap_borders_by > 0 {
-            overlap_borders(self.overlap_borders_by, container, &mut rects);
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeInclusive;
+
+    use owm_problem::{optimizer::SamplesPerCore, Weight};
+
+    use super::*;
+
+    fn layout_gen() -> LayoutGen {
+        LayoutGen::new(
+            NonZeroUsize::new(320).unwrap(),
+            NonZeroUsize::new(180).unwrap(),
+            NonZeroUsize::new(1920),
+            None,
+            0,
+            Weights {
+                gaps_weight: Weight::new(5.0).unwrap(),
+                overlap_weight: Weight::new(6.0).unwrap(),
+                area_ratios_weight: Weight::new(1.5).unwrap(),
+                aspect_ratios_weight: Weight::new(3.0).unwrap(),
+                adjacent_close_weight: Weight::new(0.5).unwrap(),
+                reading_order_weight: Weight::new(0.5).unwrap(),
+                center_main_weight: Weight::new(1.5).unwrap(),
+                consistency_weight: Weight::new(1.0).unwrap(),
+            },
+            vec![AreaRatio::new(2.0).unwrap()],
+            vec![AspectRatio::new(1.0).unwrap()],
+            0,
+            NonZeroUsize::new(1).unwrap(),
+            OptimizerConfig::default(),
+        )
+    }
+
+    fn fill_cache(gen: &mut LayoutGen, tags: u32, container: Size, counts: RangeInclusive<usize>) {
+        let now = gen.tick();
+        let profile = gen
+            .profiles
+            .get_mut(&gen.profile_key("DP-1", tags))
+            .unwrap();
+        for count in counts {
+            profile.cache.insert(
+                (container, count),
+                CacheEntry::new(
+                    Slot::finished(vec![
+                        Rect::new(0, 0, container.width, container.height);
+                        count
+                    ]),
+                    now,
+                ),
+            );
         }
-        rects
+    }
+
+    fn cached_counts(gen: &LayoutGen, tags: u32) -> Vec<usize> {
+        let mut counts = gen.profiles[&gen.profile_key("DP-1", tags)]
+            .cache
+            .keys()
+            .map(|(_, count)| *count)
+            .collect::<Vec<_>>();
+        counts.sort();
+        counts
+    }
+
+    #[test]
+    fn set_discards_only_affected_layouts() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=3);
+        gen.set([Setting::ConsistencyWeight(Weight::new(2.0).unwrap())])
+            .unwrap();
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1]);
+        gen.set([Setting::CenterMainWeight(Weight::new(2.0).unwrap())])
+            .unwrap();
+        assert_eq!(cached_counts(&gen, 0), vec![0]);
+    }
+
+    #[test]
+    fn set_keeps_layouts_if_unchanged() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=3);
+        gen.set([Setting::GapsWeight(Weight::new(5.0).unwrap())])
+            .unwrap();
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn set_validates_settings_together() {
+        let mut gen = layout_gen();
+        gen.set([
+            Setting::MinWidth(NonZeroUsize::new(2000).unwrap()),
+            Setting::MaxWidth(NonZeroUsize::new(2560)),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn set_rejects_invalid_config() {
+        let mut gen = layout_gen();
+        assert_eq!(
+            gen.set([Setting::MinWidth(NonZeroUsize::new(2000).unwrap())]),
+            Err(InvalidConfigError::MinWidthAboveMax(
+                NonZeroUsize::new(2000).unwrap(),
+                NonZeroUsize::new(1920).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn layouts_use_lowest_visible_tag_with_settings() {
+        let mut gen = layout_gen();
+        gen.set_for_tags(
+            0b100,
+            [Setting::AreaRatios(vec![AreaRatio::new(1.0).unwrap()])],
+        )
+        .unwrap();
+        gen.set_for_tags(
+            0b1000,
+            [Setting::AreaRatios(vec![AreaRatio::new(3.0).unwrap()])],
+        )
+        .unwrap();
+        assert_eq!(gen.profile_key("DP-1", 0b1).tag, None);
+        assert_eq!(gen.profile_key("DP-1", 0b111).tag, Some(2));
+        assert_eq!(gen.profile_key("DP-1", 0b1100).tag, Some(2));
+        assert_eq!(gen.profile_key("DP-1", 0b1000).tag, Some(3));
+        gen.reset_tags(0b100);
+        assert_eq!(gen.profile_key("DP-1", 0b1100).tag, Some(3));
+    }
+
+    #[test]
+    fn set_does_not_affect_tags_overriding_setting() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        gen.set_for_tags(
+            0b1,
+            [Setting::AreaRatios(vec![AreaRatio::new(1.0).unwrap()])],
+        )
+        .unwrap();
+        fill_cache(&mut gen, 0b1, container, 0..=3);
+        gen.set([Setting::AreaRatios(vec![AreaRatio::new(3.0).unwrap()])])
+            .unwrap();
+        assert_eq!(cached_counts(&gen, 0b1), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn layouts_use_output_settings_below_tag_settings() {
+        let mut gen = layout_gen();
+        gen.set_for_output("DP-1", [Setting::MaxWidth(None)])
+            .unwrap();
+        gen.set_for_output("DP-1", [Setting::MinWidth(NonZeroUsize::new(640).unwrap())])
+            .unwrap();
+        gen.set_for_tags(0b1, [Setting::MinWidth(NonZeroUsize::new(960).unwrap())])
+            .unwrap();
+        let key = gen.profile_key("DP-1", 0b1);
+        assert_eq!(
+            key,
+            ProfileKey {
+                output: Some("DP-1".to_owned()),
+                tag: Some(0)
+            }
+        );
+        assert_eq!(gen.profiles[&key].inner.max_width, None);
+        assert_eq!(gen.profiles[&key].inner.min_width.get(), 960);
+        let key = gen.profile_key("DP-1", 0b10);
+        assert_eq!(gen.profiles[&key].inner.min_width.get(), 640);
+        let key = gen.profile_key("eDP-1", 0b10);
+        assert_eq!(key.output, None);
+        assert_eq!(gen.profiles[&key].inner.min_width.get(), 320);
+        gen.reset_output("DP-1");
+        assert_eq!(gen.profile_key("DP-1", 0b10).output, None);
+    }
+
+    #[test]
+    fn fallback_layout_respects_max_size() {
+        let gen = layout_gen();
+        assert_eq!(
+            gen.fallback_layout("DP-1", 0, Size::new_checked(2560, 1440), 1),
+            vec![Rect::new_checked(320, 0, 1920, 1440)]
+        );
+    }
+
+    #[test]
+    fn fallback_layout_rescales_nearest_cached_layout() {
+        let mut gen = layout_gen();
+        fill_cache(&mut gen, 0, Size::new_checked(1920, 1080), 2..=2);
+        fill_cache(&mut gen, 0, Size::new_checked(100, 100), 2..=2);
+        assert_eq!(
+            gen.fallback_layout("DP-1", 0, Size::new_checked(960, 540), 2),
+            vec![Rect::new_checked(0, 0, 960, 540); 2]
+        );
+        assert_eq!(
+            gen.fallback_layout("DP-1", 0, Size::new_checked(960, 540), 3)
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn use_cache_file_loads_layouts_for_matching_config() {
+        let path = std::env::temp_dir()
+            .join(format!("owm-test-{}", std::process::id()))
+            .join("use_cache_file_loads_layouts_for_matching_config");
+        let _ = std::fs::remove_file(&path);
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let disk_cache = DiskCache::open(&path).unwrap();
+        let layout = [Rect::new(0, 0, container.width, container.height)];
+        disk_cache
+            .insert(hash_config(&gen.config.defaults), container, &layout)
+            .unwrap();
+        disk_cache
+            .insert(0, container, &[layout; 2].concat())
+            .unwrap();
+        drop(disk_cache);
+        gen.use_cache_file(&path).unwrap();
+        assert_eq!(cached_counts(&gen, 0), vec![1]);
+        gen.set_for_tags(0b1, [Setting::MaxWidth(None)]).unwrap();
+        assert_eq!(cached_counts(&gen, 0b1), vec![]);
+    }
+
+    #[test]
+    fn set_max_cached_layouts_evicts_least_recently_used() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 1..=2);
+        fill_cache(&mut gen, 0, other_container, 1..=2);
+        assert!(matches!(
+            gen.try_layout("DP-1", 0, container, 1),
+            Status::Finished(_)
+        ));
+        gen.set_max_cached_layouts(NonZeroUsize::new(3));
+        let cache = &gen.profiles[&gen.profile_key("DP-1", 0)].cache;
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains_key(&(container, 2)));
+    }
+
+    #[test]
+    fn set_max_cached_layouts_keeps_layouts_in_progress() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 1..=2);
+        gen.profiles
+            .get_mut(&gen.profile_key("DP-1", 0))
+            .unwrap()
+            .cache
+            .insert(
+                (container, 3),
+                CacheEntry::new(Slot::new(Priority::Speculative, false, 0), 0),
+            );
+        gen.set_max_cached_layouts(NonZeroUsize::new(1));
+        assert_eq!(cached_counts(&gen, 0), vec![2, 3]);
+    }
+
+    #[test]
+    fn precompute_starts_next_layout_up_to_max() {
+        let mut gen = layout_gen();
+        gen.set_max_precomputed_count(2);
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        gen.precompute("DP-1", 0, container, 1);
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1, 2]);
+        gen.precompute("DP-1", 0, container, 2);
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn layout_notifies_immediately_if_finished() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let notified = Arc::new(AtomicU64::new(0));
+        gen.on_finish({
+            let notified = Arc::clone(&notified);
+            move || {
+                notified.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        fill_cache(&mut gen, 0, container, 0..=1);
+        gen.layout("DP-1", 0, container, 1);
+        assert_eq!(notified.load(Ordering::Relaxed), 1);
+        gen.precompute("DP-1", 0, container, 0);
+        assert_eq!(notified.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn layout_raises_priority_of_speculative_layout() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        let slot = Arc::new(Slot::new(Priority::Speculative, false, 0));
+        gen.profiles
+            .get_mut(&gen.profile_key("DP-1", 0))
+            .unwrap()
+            .cache
+            .insert(
+                (container, 2),
+                CacheEntry {
+                    layout: Arc::clone(&slot),
+                    last_used: AtomicU64::new(0),
+                },
+            );
+        gen.layout("DP-1", 0, container, 2);
+        assert_eq!(slot.priority(), Priority::Demanded);
+        assert!(slot.finish(vec![Vec::new()]));
+    }
+
+    #[test]
+    fn try_layout_returns_intermediate_layout_until_finished() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let slot = insert_in_progress(&mut gen, container, 1);
+        assert!(matches!(
+            gen.try_layout("DP-1", 0, container, 1),
+            Status::Started
+        ));
+        let layout = vec![Rect::new(0, 0, container.width, container.height)];
+        assert!(!slot.improve(layout.clone()));
+        assert!(matches!(
+            gen.try_layout("DP-1", 0, container, 1),
+            Status::Intermediate(x) if x == layout
+        ));
+        slot.finish(vec![layout.clone()]);
+        assert!(!slot.improve(layout));
+        assert!(slot.intermediate().is_none());
+        assert!(matches!(
+            gen.try_layout("DP-1", 0, container, 1),
+            Status::Finished(_)
+        ));
+    }
+
+    #[test]
+    fn generate_blocks_until_finished_and_explain_scores_it() {
+        let mut gen = layout_gen();
+        gen.set([Setting::SamplesPerCore(SamplesPerCore::new(2).unwrap())])
+            .unwrap();
+        let container = Size::new_checked(1920, 1080);
+        let layout = gen.generate("DP-1", 0, container, 2).unwrap();
+        assert_eq!(layout.len(), 2);
+        let scores = gen.explain("DP-1", 0, container, 2).unwrap();
+        assert_eq!(scores.len(), 8);
+        assert!(gen.explain("DP-1", 0, container, 0).is_none());
+        assert!(gen.explain("DP-1", 0, container, 3).is_none());
+    }
+
+    struct NarrowWindows(Size);
+
+    impl Objective for NarrowWindows {
+        fn name(&self) -> &str {
+            "narrow windows"
+        }
+
+        fn evaluate(&self, rects: &[Rect]) -> f64 {
+            rects
+                .iter()
+                .map(|rect| rect.width().get() as f64 / self.0.width.get() as f64)
+                .sum::<f64>()
+                / rects.len() as f64
+        }
+    }
+
+    #[test]
+    fn custom_objective_changes_generated_layout() {
+        let mut gen = layout_gen();
+        gen.set([Setting::SamplesPerCore(SamplesPerCore::new(2).unwrap())])
+            .unwrap();
+        let container = Size::new_checked(1920, 1080);
+        let layout = gen.generate("DP-1", 0, container, 1).unwrap();
+
+        gen.add_objective(
+            "narrow windows",
+            Weight::new(100.0).unwrap(),
+            |container, _, _| Box::new(NarrowWindows(container)),
+        );
+        let narrow_layout = gen.generate("DP-1", 0, container, 1).unwrap();
+        let objective = NarrowWindows(container);
+        assert!(objective.evaluate(&narrow_layout) < objective.evaluate(&layout));
+        let scores = gen.explain("DP-1", 0, container, 1).unwrap();
+        assert_eq!(scores.last().unwrap().name, "narrow windows");
+    }
+
+    #[test]
+    fn pool_sizes_share_cores() {
+        assert_eq!(pool_sizes(1), (1, 1));
+        assert_eq!(pool_sizes(2), (1, 1));
+        assert_eq!(pool_sizes(7), (4, 3));
+        assert_eq!(pool_sizes(8), (4, 4));
+    }
+
+    #[test]
+    fn layout_distance_is_largest_edge_difference() {
+        let container = Size::new_checked(1000, 500);
+        let layout = [
+            Rect::new_checked(0, 0, 500, 500),
+            Rect::new_checked(500, 0, 500, 500),
+        ];
+        assert_eq!(layout_distance(container, &layout, &layout), 0.0);
+        assert_eq!(
+            layout_distance(
+                container,
+                &layout,
+                &[
+                    Rect::new_checked(0, 0, 520, 500),
+                    Rect::new_checked(520, 0, 480, 450),
+                ],
+            ),
+            0.1
+        );
+    }
+
+    #[test]
+    fn variants_are_not_too_similar() {
+        let gen = RawLayoutGen {
+            variants: NonZeroUsize::new(4).unwrap(),
+            optimizer: OptimizerConfig {
+                samples_per_core: SamplesPerCore::new(2).unwrap(),
+                ..OptimizerConfig::default()
+            },
+            ..layout_gen().config.defaults
+        };
+        let container = Size::new_checked(1920, 1080);
+        let variants = gen
+            .layout(
+                container,
+                main_stack(container, 2),
+                None,
+                0,
+                &CancellationToken::default(),
+                None,
+            )
+            .unwrap();
+        for (i, x) in variants.iter().enumerate() {
+            for y in &variants[i + 1..] {
+                assert!(layout_distance(container, x, y) >= MIN_VARIANT_DISTANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn generate_returns_none_if_generating_fails() {
+        let mut gen = layout_gen();
+        gen.add_objective("panic", Weight::new(1.0).unwrap(), |_, _, _| {
+            panic!("objective should fail for this test")
+        });
+        let container = Size::new_checked(1920, 1080);
+        assert_eq!(gen.generate("DP-1", 0, container, 2), None);
+    }
+
+    #[test]
+    fn abandon_abandons_layouts_waiting_for_it() {
+        let slot = Arc::new(Slot::new(Priority::Speculative, false, 0));
+        let next_slot = Arc::new(Slot::new(Priority::Speculative, false, 0));
+        let abandon = AbandonOnDrop(Arc::clone(&next_slot));
+        slot.then(move |_| drop(abandon));
+        let (sender, receiver) = mpsc::channel::<()>();
+        next_slot.then(move |_| drop(sender));
+        slot.abandon();
+        assert!(slot.cancel.is_cancelled());
+        assert!(next_slot.cancel.is_cancelled());
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn layout_is_no_worse_than_templates() {
+        let gen = RawLayoutGen {
+            optimizer: OptimizerConfig {
+                samples_per_core: SamplesPerCore::new(2).unwrap(),
+                ..OptimizerConfig::default()
+            },
+            ..layout_gen().config.defaults
+        };
+        let container = Size::new_checked(1920, 1080);
+        let prev_layout = main_stack(container, 2);
+        let layout = gen
+            .layout(
+                container,
+                prev_layout.clone(),
+                None,
+                0,
+                &CancellationToken::default(),
+                None,
+            )
+            .unwrap()
+            .remove(0);
+        let problem = gen.problem(container, prev_layout);
+        let best_template = templates::all(container, 3)
+            .into_iter()
+            .map(|rects| problem.evaluate(&gen.fit_max_size(container, rects)))
+            .min_by(f64::total_cmp)
+            .unwrap();
+        assert!(problem.evaluate(&layout) <= best_template);
+    }
+
+    fn insert_in_progress(gen: &mut LayoutGen, container: Size, count: usize) -> Arc<Slot> {
+        let slot = Arc::new(Slot::new(Priority::Speculative, false, 0));
+        gen.profiles
+            .get_mut(&gen.profile_key("DP-1", 0))
+            .unwrap()
+            .cache
+            .insert(
+                (container, count),
+                CacheEntry {
+                    layout: Arc::clone(&slot),
+                    last_used: AtomicU64::new(0),
+                },
+            );
+        slot
+    }
+
+    #[test]
+    fn layout_cancels_layouts_for_previous_container() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        fill_cache(&mut gen, 0, other_container, 0..=1);
+        let slot = insert_in_progress(&mut gen, container, 2);
+        gen.layout("DP-1", 0, container, 2);
+        gen.layout("DP-1", 0, other_container, 1);
+        assert!(slot.cancel.is_cancelled());
+        assert!(!gen.profiles[&gen.profile_key("DP-1", 0)]
+            .cache
+            .contains_key(&(container, 2)));
+    }
+
+    #[test]
+    fn layout_keeps_layouts_used_by_other_outputs() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        fill_cache(&mut gen, 0, other_container, 0..=1);
+        let slot = insert_in_progress(&mut gen, container, 2);
+        gen.layout("DP-1", 0, container, 2);
+        gen.layout("DP-2", 0, container, 2);
+        gen.layout("DP-1", 0, other_container, 1);
+        assert!(!slot.cancel.is_cancelled());
+    }
+
+    #[test]
+    fn set_cancels_layouts_it_affects() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        let slot = insert_in_progress(&mut gen, container, 2);
+        gen.set([Setting::ConsistencyWeight(Weight::new(2.0).unwrap())])
+            .unwrap();
+        assert!(slot.cancel.is_cancelled());
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1]);
+    }
+
+    #[test]
+    fn reset_tags_cancels_layouts_of_removed_profile() {
+        let mut gen = layout_gen();
+        gen.set_for_tags(0b1, [Setting::MaxWidth(None)]).unwrap();
+        let container = Size::new_checked(1920, 1080);
+        let slot = Arc::new(Slot::new(Priority::Speculative, false, 0));
+        gen.profiles
+            .get_mut(&gen.profile_key("DP-1", 0b1))
+            .unwrap()
+            .cache
+            .insert(
+                (container, 1),
+                CacheEntry {
+                    layout: Arc::clone(&slot),
+                    last_used: AtomicU64::new(0),
+                },
+            );
+        gen.reset_tags(0b1);
+        assert!(slot.cancel.is_cancelled());
+    }
+
+    #[test]
+    fn remove_output_cancels_its_layouts() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        fill_cache(&mut gen, 0, other_container, 0..=1);
+        let slot = insert_in_progress(&mut gen, container, 2);
+        gen.layout("DP-1", 0, container, 2);
+        gen.layout("DP-2", 0, container, 2);
+        gen.remove_output("DP-1");
+        assert!(!slot.cancel.is_cancelled());
+        gen.remove_output("DP-2");
+        assert!(slot.cancel.is_cancelled());
+        assert!(gen.demanded.is_empty());
+    }
+
+    #[test]
+    fn removed_output_does_not_keep_layouts_in_progress() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 0..=1);
+        fill_cache(&mut gen, 0, other_container, 0..=1);
+        let slot = insert_in_progress(&mut gen, container, 2);
+        gen.layout("DP-1", 0, container, 2);
+        gen.layout("DP-2", 0, container, 2);
+        gen.remove_output("DP-2");
+        gen.layout("DP-1", 0, other_container, 1);
+        assert!(slot.cancel.is_cancelled());
+    }
+
+    #[test]
+    fn reroll_discards_layout_and_higher_counts() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        let other_container = Size::new_checked(1920, 1050);
+        fill_cache(&mut gen, 0, container, 0..=4);
+        fill_cache(&mut gen, 0, other_container, 0..=4);
+        let profile = gen
+            .profiles
+            .get_mut(&ProfileKey {
+                output: None,
+                tag: None,
+            })
+            .unwrap();
+        profile.variants.insert((container, 1), 1);
+        profile.variants.insert((container, 3), 1);
+        profile.variants.insert((other_container, 3), 1);
+        assert_eq!(profile.reroll(container, 2), Some(4));
+        assert_eq!(profile.reroll(container, 0), None);
+        assert_eq!(profile.rerolls[&(container, 2)], 1);
+        assert_eq!(
+            profile.variants,
+            HashMap::from([((container, 1), 1), ((other_container, 3), 1)])
+        );
+        assert_eq!(cached_counts(&gen, 0), vec![0, 0, 1, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn cycle_variant_discards_layouts_from_previous_variant() {
+        let mut gen = layout_gen();
+        let container = Size::new_checked(1920, 1080);
+        fill_cache(&mut gen, 0, container, 0..=3);
+        let variants = vec![
+            vec![Rect::new_checked(0, 0, 1920, 1080)],
+            vec![Rect::new_checked(0, 0, 960, 1080)],
+        ];
+        let slot = Slot::new(Priority::Speculative, false, 0);
+        slot.finish(variants.clone());
+        let profile = gen
+            .profiles
+            .get_mut(&ProfileKey {
+                output: None,
+                tag: None,
+            })
+            .unwrap();
+        profile
+            .cache
+            .insert((container, 1), CacheEntry::new(slot, 0));
+        profile.variants.insert((container, 2), 1);
+        assert_eq!(profile.cycle_variant(container, 1, false), Some(3));
+        assert_eq!(profile.variants, HashMap::from([((container, 1), 1)]));
+        assert_eq!(
+            profile.cache[&(container, 1)].layout.get(),
+            Some(&variants[1])
+        );
+        assert_eq!(profile.cycle_variant(container, 1, true), Some(1));
+        assert_eq!(
+            profile.cache[&(container, 1)].layout.get(),
+            Some(&variants[0])
+        );
+        assert_eq!(profile.cycle_variant(container, 0, true), None);
+        assert_eq!(cached_counts(&gen, 0), vec![0, 1]);
     }
 }
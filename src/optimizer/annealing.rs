@@ -0,0 +1,117 @@
+use owm_problem::{encoding::Decoder, objective::Problem, optimizer::OptimizerConfig, Rect};
+use rand::prelude::*;
+use rand_xoshiro::SplitMix64;
+use rayon::prelude::*;
+
+use super::{cores, Control, Optimizer};
+
+/// Steps each chain takes
+/// per sample per core.
+const STEPS_PER_SAMPLE: usize = 100;
+
+/// Random layouts evaluated
+/// to choose the initial temperature.
+const TEMPERATURE_SAMPLES: usize = 100;
+
+/// Steps between checks for cancellation
+/// and the deadline.
+const CANCEL_INTERVAL: usize = 64;
+
+/// Simulated annealing
+/// over positions and sizes,
+/// one chain per core,
+/// cooling linearly.
+/// Chains start from hints,
+/// then random layouts.
+///
+/// Each chain takes `samples_per_core` × 100 steps.
+#[derive(Clone, Debug)]
+pub struct Annealing {
+    config: OptimizerConfig,
+}
+
+impl Annealing {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Optimizer for Annealing {
+    fn optimize(
+        &self,
+        decoder: &Decoder,
+        problem: &Problem,
+        hints: &[Vec<Rect>],
+        rng: &mut SplitMix64,
+        control: &Control,
+    ) -> Option<Vec<Rect>> {
+        let steps = self.config.samples_per_core.into_inner() * STEPS_PER_SAMPLE;
+        let evaluate = |xs: &[f64]| problem.evaluate(&decoder.decode_reals(xs));
+        let random_point = |rng: &mut SplitMix64| {
+            (0..decoder.reals())
+                .map(|_| rng.gen::<f64>())
+                .collect::<Vec<_>>()
+        };
+
+        // Worse layouts are accepted
+        // about as often as random layouts differ.
+        let values = (0..TEMPERATURE_SAMPLES)
+            .map(|_| evaluate(&random_point(rng)))
+            .collect::<Vec<_>>();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let initial_temperature = (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+            / values.len() as f64)
+            .sqrt()
+            .max(f64::EPSILON);
+
+        let seeds = (0..cores()).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+        seeds
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                let mut rng = SplitMix64::seed_from_u64(seed);
+                let mut point = match hints.get(i) {
+                    Some(hint) => decoder.encode_reals(hint),
+                    None => random_point(&mut rng),
+                };
+                let mut value = evaluate(&point);
+                control.report(value, || decoder.decode_reals(&point));
+                let mut best = (value, point.clone());
+                for step in 0..steps {
+                    if step % CANCEL_INTERVAL == 0 {
+                        if control.is_cancelled() {
+                            return None;
+                        }
+                        if control.is_past_deadline() {
+                            break;
+                        }
+                    }
+                    let remaining = 1.0 - step as f64 / steps as f64;
+                    let temperature = initial_temperature * remaining;
+                    // Large moves explore early,
+                    // small moves refine late.
+                    let radius = 0.5 * remaining + 0.001;
+                    let i = rng.gen_range(0..point.len());
+                    let old = point[i];
+                    point[i] = (old + rng.gen_range(-radius..=radius)).clamp(0.0, 1.0);
+                    let new_value = evaluate(&point);
+                    if new_value <= value
+                        || rng.gen::<f64>() < ((value - new_value) / temperature).exp()
+                    {
+                        value = new_value;
+                        if value < best.0 {
+                            control.report(value, || decoder.decode_reals(&point));
+                            best = (value, point.clone());
+                        }
+                    } else {
+                        point[i] = old;
+                    }
+                }
+                Some(best)
+            })
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .min_by(|(x, _), (y, _)| x.total_cmp(y))
+            .map(|(_, point)| decoder.decode_reals(&point))
+    }
+}
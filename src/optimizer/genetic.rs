@@ -0,0 +1,112 @@
+use ndarray::prelude::*;
+use owm_problem::{encoding::Decoder, objective::Problem, optimizer::OptimizerConfig, Rect};
+use rand::prelude::*;
+use rand_xoshiro::SplitMix64;
+use rayon::prelude::*;
+
+use super::{converged, cores, Control, Optimizer};
+
+/// Generations before giving up on convergence,
+/// in case mutation keeps the population diverse.
+const MAX_GENERATIONS: usize = 1000;
+
+/// Genetic algorithm over bits,
+/// with tournament selection,
+/// uniform crossover,
+/// and elitism.
+///
+/// The population is `num_samples`,
+/// starting with hints,
+/// and optimization stops
+/// when each bit is at least `converged_threshold`
+/// the same across the population,
+/// or at the deadline.
+#[derive(Clone, Debug)]
+pub struct Genetic {
+    config: OptimizerConfig,
+}
+
+impl Genetic {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Optimizer for Genetic {
+    fn optimize(
+        &self,
+        decoder: &Decoder,
+        problem: &Problem,
+        hints: &[Vec<Rect>],
+        rng: &mut SplitMix64,
+        control: &Control,
+    ) -> Option<Vec<Rect>> {
+        let size = self.config.num_samples(cores());
+        let bits = decoder.bits();
+        let mutation_chance = self.config.mutation_chance.into_inner();
+        let threshold = self.config.converged_threshold.into_inner();
+        let mut population = Array2::from_shape_simple_fn((size, bits), || rng.gen::<bool>());
+        // Elitism keeps hints
+        // until something better is found.
+        for (mut row, hint) in population.rows_mut().into_iter().zip(hints) {
+            row.assign(&decoder.encode(hint));
+        }
+        let mut best: Option<(f64, Array1<bool>)> = None;
+        for _ in 0..MAX_GENERATIONS {
+            if control.is_cancelled() {
+                return None;
+            }
+            let values = (0..size)
+                .into_par_iter()
+                .map(|i| problem.evaluate(decoder.decode1(population.row(i)).as_slice().unwrap()))
+                .collect::<Vec<_>>();
+            let (i, value) = values
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by(|(_, x), (_, y)| x.total_cmp(y))
+                .expect("population should not be empty");
+            if !best.as_ref().is_some_and(|(x, _)| *x <= value) {
+                control.report(value, || decoder.decode1(population.row(i)).into_raw_vec());
+                best = Some((value, population.row(i).to_owned()));
+            }
+            if control.is_past_deadline()
+                || converged(
+                    threshold,
+                    population
+                        .columns()
+                        .into_iter()
+                        .map(|xs| xs.iter().filter(|x| **x).count() as f64 / size as f64),
+                )
+            {
+                break;
+            }
+            let tournament = |rng: &mut SplitMix64| {
+                let (a, b) = (rng.gen_range(0..size), rng.gen_range(0..size));
+                if values[a] <= values[b] {
+                    a
+                } else {
+                    b
+                }
+            };
+            let mut next = Array2::from_elem((size, bits), false);
+            next.row_mut(0)
+                .assign(&best.as_ref().expect("best should be set").1);
+            for mut child in next.rows_mut().into_iter().skip(1) {
+                let (a, b) = (tournament(rng), tournament(rng));
+                for (j, bit) in child.iter_mut().enumerate() {
+                    *bit = if rng.gen() {
+                        population[[a, j]]
+                    } else {
+                        population[[b, j]]
+                    };
+                    if mutation_chance > 0.0 && rng.gen_bool(mutation_chance) {
+                        *bit = !*bit;
+                    }
+                }
+            }
+            population = next;
+        }
+        best.map(|(_, point)| decoder.decode1(point.view()).into_raw_vec())
+    }
+}
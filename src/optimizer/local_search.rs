@@ -0,0 +1,84 @@
+use ndarray::prelude::*;
+use owm_problem::{encoding::Decoder, objective::Problem, optimizer::OptimizerConfig, Rect};
+use rand::prelude::*;
+use rand_xoshiro::SplitMix64;
+use rayon::prelude::*;
+
+use super::{cores, Control, Optimizer};
+
+/// Samples per restart.
+const SAMPLES_PER_RESTART: usize = 100;
+
+/// Steepest-descent hill climbing over bits,
+/// flipping one bit at a time,
+/// restarted from hints,
+/// then random layouts.
+///
+/// It restarts once
+/// per 100 samples.
+#[derive(Clone, Debug)]
+pub struct LocalSearch {
+    config: OptimizerConfig,
+}
+
+impl LocalSearch {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Optimizer for LocalSearch {
+    fn optimize(
+        &self,
+        decoder: &Decoder,
+        problem: &Problem,
+        hints: &[Vec<Rect>],
+        rng: &mut SplitMix64,
+        control: &Control,
+    ) -> Option<Vec<Rect>> {
+        let restarts = (self.config.num_samples(cores()) / SAMPLES_PER_RESTART).max(1);
+        let evaluate = |point: &Array1<bool>| {
+            problem.evaluate(decoder.decode1(point.view()).as_slice().unwrap())
+        };
+        let seeds = (0..restarts).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+        seeds
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                let mut rng = SplitMix64::seed_from_u64(seed);
+                let mut point = match hints.get(i) {
+                    Some(hint) => decoder.encode(hint),
+                    None => Array1::from_shape_simple_fn(decoder.bits(), || rng.gen::<bool>()),
+                };
+                let mut value = evaluate(&point);
+                control.report(value, || decoder.decode1(point.view()).into_raw_vec());
+                loop {
+                    if control.is_cancelled() {
+                        return None;
+                    }
+                    if control.is_past_deadline() {
+                        break;
+                    }
+                    let Some((i, new_value)) = (0..point.len())
+                        .map(|i| {
+                            let mut neighbor = point.clone();
+                            neighbor[i] = !neighbor[i];
+                            (i, evaluate(&neighbor))
+                        })
+                        .min_by(|(_, x), (_, y)| x.total_cmp(y))
+                        .filter(|(_, x)| *x < value)
+                    else {
+                        break;
+                    };
+                    point[i] = !point[i];
+                    value = new_value;
+                    control.report(value, || decoder.decode1(point.view()).into_raw_vec());
+                }
+                Some((value, point))
+            })
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .min_by(|(x, _), (y, _)| x.total_cmp(y))
+            .map(|(_, point)| decoder.decode1(point.view()).into_raw_vec())
+    }
+}
@@ -0,0 +1,246 @@
+//! Searches for layouts
+//! minimizing a `Problem`.
+
+mod annealing;
+mod genetic;
+mod local_search;
+mod pbil;
+mod polish;
+
+use std::{sync::Mutex, time::Instant};
+
+use owm_problem::{
+    encoding::Decoder,
+    objective::Problem,
+    optimizer::{Algorithm, OptimizerConfig},
+    Rect,
+};
+use rand_xoshiro::SplitMix64;
+
+pub use self::{
+    annealing::Annealing, genetic::Genetic, local_search::LocalSearch, pbil::Pbil, polish::polish,
+};
+
+/// A search for the layout
+/// minimizing a problem.
+pub trait Optimizer {
+    /// Return the best layout found
+    /// among layouts `decoder` can decode,
+    /// or `None` if cancelled.
+    ///
+    /// The search starts near `hints`,
+    /// layouts likely similar to the best,
+    /// with as many windows as `decoder` decodes.
+    ///
+    /// Results depend only on the inputs
+    /// and the state of `rng`,
+    /// unless stopped by a deadline.
+    fn optimize(
+        &self,
+        decoder: &Decoder,
+        problem: &Problem,
+        hints: &[Vec<Rect>],
+        rng: &mut SplitMix64,
+        control: &Control,
+    ) -> Option<Vec<Rect>>;
+}
+
+/// Limits and observers
+/// for a running optimizer.
+pub struct Control<'a> {
+    is_cancelled: &'a (dyn Fn() -> bool + Sync),
+    deadline: Option<Instant>,
+    on_improvement: Option<&'a (dyn Fn(Vec<Rect>) + Sync)>,
+    /// Value of the last layout reported.
+    best: Mutex<f64>,
+}
+
+impl<'a> Control<'a> {
+    /// Optimize until finished,
+    /// or until `is_cancelled` returns true.
+    pub fn new(is_cancelled: &'a (dyn Fn() -> bool + Sync)) -> Self {
+        Self {
+            is_cancelled,
+            deadline: None,
+            on_improvement: None,
+            best: Mutex::new(f64::INFINITY),
+        }
+    }
+
+    /// Return the best layout found so far
+    /// after `deadline`,
+    /// if any.
+    pub fn with_deadline(self, deadline: Option<Instant>) -> Self {
+        Self { deadline, ..self }
+    }
+
+    /// Call `f` with each layout found
+    /// better than those before.
+    pub fn with_on_improvement(self, f: &'a (dyn Fn(Vec<Rect>) + Sync)) -> Self {
+        Self {
+            on_improvement: Some(f),
+            ..self
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        (self.is_cancelled)()
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|x| Instant::now() >= x)
+    }
+
+    /// Report a layout with objective value `value`,
+    /// decoding it with `rects`
+    /// only if it improves on those before.
+    fn report(&self, value: f64, rects: impl FnOnce() -> Vec<Rect>) {
+        if let Some(f) = self.on_improvement {
+            let mut best = self.best.lock().unwrap();
+            if value < *best {
+                *best = value;
+                f(rects())
+            }
+        }
+    }
+}
+
+/// Return the optimizer `config` selects.
+pub fn from_config(config: OptimizerConfig) -> Box<dyn Optimizer + Send + Sync> {
+    match config.algorithm {
+        Algorithm::Pbil => Box::new(Pbil::new(config)),
+        Algorithm::Genetic => Box::new(Genetic::new(config)),
+        Algorithm::Annealing => Box::new(Annealing::new(config)),
+        Algorithm::LocalSearch => Box::new(LocalSearch::new(config)),
+    }
+}
+
+/// Return the number of CPU cores available.
+pub(crate) fn cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |x| x.into())
+}
+
+/// Return whether every probability
+/// is at least `threshold` from even.
+fn converged(threshold: f64, probabilities: impl IntoIterator<Item = f64>) -> bool {
+    probabilities
+        .into_iter()
+        .all(|p| p >= threshold || p <= 1.0 - threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use owm_problem::{optimizer::SamplesPerCore, AreaRatio, AspectRatio, Size, Weight, Weights};
+    use rand::SeedableRng;
+
+    use super::*;
+
+    pub(super) fn problem(container: Size, count: usize) -> (Decoder, Problem) {
+        let min_size = Size::new_checked(320, 180);
+        (
+            Decoder::new(min_size, container, container, count),
+            Problem::new(
+                Weights {
+                    gaps_weight: Weight::new(5.0).unwrap(),
+                    overlap_weight: Weight::new(6.0).unwrap(),
+                    area_ratios_weight: Weight::new(1.5).unwrap(),
+                    aspect_ratios_weight: Weight::new(3.0).unwrap(),
+                    adjacent_close_weight: Weight::new(0.5).unwrap(),
+                    reading_order_weight: Weight::new(0.5).unwrap(),
+                    center_main_weight: Weight::new(1.5).unwrap(),
+                    consistency_weight: Weight::new(1.0).unwrap(),
+                },
+                vec![AreaRatio::new(2.0).unwrap()],
+                vec![AspectRatio::new(1.0).unwrap()],
+                container,
+                container,
+                owm_problem::templates::main_stack(container, count - 1),
+            ),
+        )
+    }
+
+    #[test]
+    fn optimizers_return_count_rects_in_container() {
+        let container = Size::new_checked(1920, 1080);
+        let (decoder, problem) = problem(container, 2);
+        for algorithm in Algorithm::ALL {
+            let rects = from_config(OptimizerConfig {
+                algorithm,
+                samples_per_core: SamplesPerCore::new(8).unwrap(),
+                ..OptimizerConfig::default()
+            })
+            .optimize(
+                &decoder,
+                &problem,
+                &[],
+                &mut SplitMix64::seed_from_u64(0),
+                &Control::new(&|| false),
+            )
+            .unwrap();
+            assert_eq!(rects.len(), 2, "{algorithm}");
+            assert!(
+                rects
+                    .iter()
+                    .all(|x| x.right() <= container.width.get()
+                        && x.bottom() <= container.height.get()),
+                "{algorithm}"
+            );
+        }
+    }
+
+    #[test]
+    fn optimizers_stop_when_cancelled() {
+        let container = Size::new_checked(1920, 1080);
+        let (decoder, problem) = problem(container, 2);
+        for algorithm in Algorithm::ALL {
+            assert_eq!(
+                from_config(OptimizerConfig {
+                    algorithm,
+                    ..OptimizerConfig::default()
+                })
+                .optimize(
+                    &decoder,
+                    &problem,
+                    &[],
+                    &mut SplitMix64::seed_from_u64(0),
+                    &Control::new(&|| true),
+                ),
+                None,
+                "{algorithm}"
+            );
+        }
+    }
+
+    #[test]
+    fn optimizers_return_best_so_far_after_deadline() {
+        let container = Size::new_checked(1920, 1080);
+        let (decoder, problem) = problem(container, 2);
+        for algorithm in Algorithm::ALL {
+            let improvements = Mutex::new(Vec::new());
+            let on_improvement =
+                |rects: Vec<Rect>| improvements.lock().unwrap().push(problem.evaluate(&rects));
+            let rects = from_config(OptimizerConfig {
+                algorithm,
+                samples_per_core: SamplesPerCore::new(8).unwrap(),
+                ..OptimizerConfig::default()
+            })
+            .optimize(
+                &decoder,
+                &problem,
+                &[],
+                &mut SplitMix64::seed_from_u64(0),
+                &Control::new(&|| false)
+                    .with_deadline(Some(Instant::now()))
+                    .with_on_improvement(&on_improvement),
+            )
+            .unwrap();
+            let improvements = improvements.into_inner().unwrap();
+            assert!(!improvements.is_empty(), "{algorithm}");
+            assert!(
+                improvements.windows(2).all(|xs| xs[1] < xs[0]),
+                "{algorithm}"
+            );
+            assert_eq!(rects.len(), 2, "{algorithm}");
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use ndarray::prelude::*;
+use owm_problem::{encoding::Decoder, objective::Problem, optimizer::OptimizerConfig, Rect};
+use rand::prelude::*;
+use rand_xoshiro::SplitMix64;
+use rayon::prelude::*;
+
+use super::{converged, cores, Control, Optimizer};
+
+/// How far hints move initial probabilities
+/// from even.
+/// Probabilities stay short of certain,
+/// so other layouts can still be found.
+const HINT_WEIGHT: f64 = 0.25;
+
+/// Population-based incremental learning
+/// over bits.
+///
+/// Each step samples `num_samples` points
+/// and moves probabilities toward the best,
+/// until converged or the deadline.
+#[derive(Clone, Debug)]
+pub struct Pbil {
+    config: OptimizerConfig,
+}
+
+impl Pbil {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Optimizer for Pbil {
+    fn optimize(
+        &self,
+        decoder: &Decoder,
+        problem: &Problem,
+        hints: &[Vec<Rect>],
+        rng: &mut SplitMix64,
+        control: &Control,
+    ) -> Option<Vec<Rect>> {
+        let num_samples = self.config.num_samples(cores());
+        let adjust_rate = self.config.adjust_rate.into_inner();
+        let mutation_chance = self.config.mutation_chance.into_inner();
+        let mutation_adjust_rate = self.config.mutation_adjust_rate.into_inner();
+        let threshold = self.config.converged_threshold.into_inner();
+        let mut probabilities = initial_probabilities(decoder, hints);
+        // Best sample,
+        // returned if stopped early.
+        let mut best: Option<(f64, Array1<bool>)> = None;
+        while !converged(threshold, probabilities.iter().copied()) {
+            if control.is_cancelled() {
+                return None;
+            }
+            if control.is_past_deadline() {
+                if let Some((_, point)) = best {
+                    return Some(decoder.decode1(point.view()).into_raw_vec());
+                }
+            }
+            let samples = Array2::from_shape_fn((num_samples, probabilities.len()), |(_, j)| {
+                rng.gen::<f64>() < probabilities[j]
+            });
+            let (i, value) = (0..num_samples)
+                .into_par_iter()
+                .map(|i| problem.evaluate(decoder.decode1(samples.row(i)).as_slice().unwrap()))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .enumerate()
+                .min_by(|(_, x), (_, y)| x.total_cmp(y))
+                .expect("samples should not be empty");
+            let sample = samples.row(i);
+            if !best.as_ref().is_some_and(|(x, _)| *x <= value) {
+                control.report(value, || decoder.decode1(sample).into_raw_vec());
+                best = Some((value, sample.to_owned()));
+            }
+            for (p, bit) in probabilities.iter_mut().zip(sample) {
+                *p = adjust(*p, *bit, adjust_rate);
+            }
+            if mutation_chance > 0.0 {
+                for p in probabilities.iter_mut() {
+                    if rng.gen_bool(mutation_chance) {
+                        *p = adjust(*p, rng.gen(), mutation_adjust_rate);
+                    }
+                }
+            }
+        }
+        Some(
+            decoder
+                .decode1(probabilities.mapv(|p| p >= 0.5).view())
+                .into_raw_vec(),
+        )
+    }
+}
+
+/// Return even probabilities
+/// moved toward bits of `hints`.
+fn initial_probabilities(decoder: &Decoder, hints: &[Vec<Rect>]) -> Array1<f64> {
+    let mut probabilities = Array1::from_elem(decoder.bits(), 0.5);
+    for hint in hints {
+        let step = HINT_WEIGHT / hints.len() as f64;
+        for (p, bit) in probabilities.iter_mut().zip(decoder.encode(hint)) {
+            *p += if bit { step } else { -step };
+        }
+    }
+    probabilities
+}
+
+/// Move probability `p` toward `bit` by `rate`.
+fn adjust(p: f64, bit: bool, rate: f64) -> f64 {
+    p + rate * (if bit { 1.0 } else { 0.0 } - p)
+}
+
+#[cfg(test)]
+mod tests {
+    use owm_problem::Size;
+
+    use super::*;
+
+    #[test]
+    fn initial_probabilities_lean_toward_hints() {
+        let container = Size::new_checked(1920, 1080);
+        let decoder = Decoder::new(Size::new_checked(320, 180), container, container, 1);
+        let hint = vec![Rect::new_checked(0, 0, 1920, 1080)];
+        let bits = decoder.encode(&hint);
+        let probabilities = initial_probabilities(&decoder, &[hint]);
+        assert!(probabilities.iter().zip(bits).all(|(p, bit)| if bit {
+            *p > 0.5
+        } else {
+            *p < 0.5
+        }));
+        assert!(probabilities
+            .iter()
+            .all(|p| (0.5 - HINT_WEIGHT..=0.5 + HINT_WEIGHT).contains(p)));
+    }
+}
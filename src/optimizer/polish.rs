@@ -0,0 +1,139 @@
+use std::num::NonZeroUsize;
+
+use owm_problem::{encoding::Decoder, objective::Problem, Rect};
+
+use super::Control;
+
+/// Largest distance in pixels
+/// an edge is moved at once.
+/// It is halved
+/// whenever no move improves the layout,
+/// down to one pixel.
+const MAX_STEP: usize = 64;
+
+/// Return `rects` refined
+/// by moving window edges in pixels,
+/// keeping each move that improves `problem`,
+/// or `None` if cancelled.
+///
+/// Windows stay in the bounds of `decoder`.
+/// Unlike optimizers,
+/// this can reach positions and sizes
+/// between those `decoder` can decode.
+/// It stops early at the deadline.
+pub fn polish(
+    decoder: &Decoder,
+    problem: &Problem,
+    mut rects: Vec<Rect>,
+    control: &Control,
+) -> Option<Vec<Rect>> {
+    let mut value = problem.evaluate(&rects);
+    let mut step = MAX_STEP;
+    while step > 0 {
+        let mut improved = false;
+        for i in 0..rects.len() {
+            for edges in MOVES {
+                for delta in [-(step as isize), step as isize] {
+                    if control.is_cancelled() {
+                        return None;
+                    }
+                    if control.is_past_deadline() {
+                        return Some(rects);
+                    }
+                    let Some(rect) = nudge(decoder, rects[i], edges, delta) else {
+                        continue;
+                    };
+                    let old = std::mem::replace(&mut rects[i], rect);
+                    let new_value = problem.evaluate(&rects);
+                    if new_value < value {
+                        value = new_value;
+                        improved = true;
+                        control.report(value, || rects.clone());
+                    } else {
+                        rects[i] = old;
+                    }
+                }
+            }
+        }
+        if !improved {
+            step /= 2;
+        }
+    }
+    Some(rects)
+}
+
+/// Edges moved together,
+/// left, top, right, and bottom.
+/// Moving opposite edges together
+/// moves the window.
+const MOVES: [[bool; 4]; 6] = [
+    [true, false, false, false],
+    [false, true, false, false],
+    [false, false, true, false],
+    [false, false, false, true],
+    [true, false, true, false],
+    [false, true, false, true],
+];
+
+/// Return `rect` with `edges` moved by `delta`,
+/// or `None` if it would leave the bounds of `decoder`.
+fn nudge(decoder: &Decoder, rect: Rect, edges: [bool; 4], delta: isize) -> Option<Rect> {
+    let shift = |x: usize, moved: bool| {
+        if moved {
+            x.checked_add_signed(delta)
+        } else {
+            Some(x)
+        }
+    };
+    let left = shift(rect.left(), edges[0])?;
+    let top = shift(rect.top(), edges[1])?;
+    let right = shift(rect.right(), edges[2])?;
+    let bottom = shift(rect.bottom(), edges[3])?;
+    let container = decoder.container();
+    let (min_size, max_size) = (decoder.min_size(), decoder.max_size());
+    let width = NonZeroUsize::new(right.checked_sub(left)?)?;
+    let height = NonZeroUsize::new(bottom.checked_sub(top)?)?;
+    (right <= container.width.get()
+        && bottom <= container.height.get()
+        && (min_size.width..=max_size.width).contains(&width)
+        && (min_size.height..=max_size.height).contains(&height))
+    .then(|| Rect::new(left, top, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use owm_problem::{templates::main_stack, Size};
+
+    use super::{super::tests::problem, *};
+
+    #[test]
+    fn polish_improves_within_bounds() {
+        let container = Size::new_checked(1920, 1080);
+        let (decoder, problem) = problem(container, 2);
+        let rects = vec![
+            Rect::new_checked(0, 0, 1000, 700),
+            Rect::new_checked(1100, 300, 500, 500),
+        ];
+        let polished = polish(&decoder, &problem, rects.clone(), &Control::new(&|| false)).unwrap();
+        assert!(problem.evaluate(&polished) < problem.evaluate(&rects));
+        assert!(polished.iter().all(|x| x.right() <= 1920
+            && x.bottom() <= 1080
+            && x.width().get() >= 320
+            && x.height().get() >= 180));
+    }
+
+    #[test]
+    fn polish_stops_when_cancelled() {
+        let container = Size::new_checked(1920, 1080);
+        let (decoder, problem) = problem(container, 2);
+        assert_eq!(
+            polish(
+                &decoder,
+                &problem,
+                main_stack(container, 2),
+                &Control::new(&|| true)
+            ),
+            None
+        );
+    }
+}
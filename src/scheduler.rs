@@ -0,0 +1,216 @@
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use crate::Priority;
+
+/// Runs jobs on a fixed number of threads,
+/// most urgent first.
+///
+/// Threads stop when this is dropped,
+/// discarding jobs not yet started.
+pub struct Scheduler {
+    spawner: Spawner,
+}
+
+/// Adds jobs to a `Scheduler`.
+#[derive(Clone)]
+pub struct Spawner {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    /// Maximum number of speculative jobs
+    /// running at once,
+    /// so urgent jobs can always start.
+    max_speculative: usize,
+}
+
+struct State {
+    jobs: Vec<Job>,
+    next_id: u64,
+    speculative_running: usize,
+    closed: bool,
+}
+
+struct Job {
+    /// Jobs with equal priority
+    /// run in order of id.
+    id: u64,
+    /// Priority may change
+    /// while the job waits.
+    priority: Box<dyn Fn() -> Priority + Send>,
+    run: Box<dyn FnOnce(Priority) + Send>,
+}
+
+impl Scheduler {
+    pub fn new(workers: NonZeroUsize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                jobs: Vec::new(),
+                next_id: 0,
+                speculative_running: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            max_speculative: (workers.get() - 1).max(1),
+        });
+        for i in 0..workers.get() {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name(format!("owm-worker-{i}"))
+                .spawn(move || shared.work())
+                .expect("worker thread should spawn");
+        }
+        Self {
+            spawner: Spawner { shared },
+        }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        self.spawner.clone()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.spawner.shared.state.lock().unwrap().closed = true;
+        self.spawner.shared.changed.notify_all();
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler").finish_non_exhaustive()
+    }
+}
+
+impl Spawner {
+    /// Run `job` when a thread is available
+    /// and no job with higher `priority` is waiting.
+    ///
+    /// `job` receives its priority
+    /// when it started.
+    pub fn spawn(
+        &self,
+        priority: impl Fn() -> Priority + Send + 'static,
+        job: impl FnOnce(Priority) + Send + 'static,
+    ) {
+        let mut state = self.shared.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.push(Job {
+            id,
+            priority: Box::new(priority),
+            run: Box::new(job),
+        });
+        drop(state);
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Shared {
+    fn work(&self) {
+        loop {
+            let (job, priority) = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if state.closed {
+                        return;
+                    }
+                    if let Some(x) = self.take_next(&mut state) {
+                        break x;
+                    }
+                    state = self.changed.wait(state).unwrap();
+                }
+            };
+            // A panic loses only the job,
+            // not the thread.
+            let _ = catch_unwind(AssertUnwindSafe(|| (job.run)(priority)));
+            if priority == Priority::Speculative {
+                self.state.lock().unwrap().speculative_running -= 1;
+                self.changed.notify_all();
+            }
+        }
+    }
+
+    fn take_next(&self, state: &mut State) -> Option<(Job, Priority)> {
+        let (i, priority) = state
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(i, job)| (i, (job.priority)()))
+            .max_by_key(|(i, priority)| (*priority, std::cmp::Reverse(state.jobs[*i].id)))?;
+        if priority == Priority::Speculative {
+            if state.speculative_running >= self.max_speculative {
+                return None;
+            }
+            state.speculative_running += 1;
+        }
+        Some((state.jobs.swap_remove(i), priority))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Sender};
+
+    use super::*;
+
+    fn record(order: &Sender<&'static str>, name: &'static str) -> impl FnOnce(Priority) {
+        let order = order.clone();
+        move |_| order.send(name).unwrap()
+    }
+
+    #[test]
+    fn scheduler_runs_demanded_jobs_first() {
+        let scheduler = Scheduler::new(NonZeroUsize::new(1).unwrap());
+        let spawner = scheduler.spawner();
+        let (order, recorded) = channel();
+        let (unblock, blocked) = channel::<()>();
+        let blocked = Mutex::new(blocked);
+        spawner.spawn(
+            || Priority::Demanded,
+            move |_| blocked.lock().unwrap().recv().unwrap(),
+        );
+        spawner.spawn(|| Priority::Speculative, record(&order, "speculative"));
+        spawner.spawn(|| Priority::Demanded, record(&order, "demanded 1"));
+        spawner.spawn(|| Priority::Demanded, record(&order, "demanded 2"));
+        unblock.send(()).unwrap();
+        assert_eq!(
+            recorded.iter().take(3).collect::<Vec<_>>(),
+            vec!["demanded 1", "demanded 2", "speculative"]
+        );
+    }
+
+    #[test]
+    fn scheduler_keeps_a_worker_for_demanded_jobs() {
+        let scheduler = Scheduler::new(NonZeroUsize::new(2).unwrap());
+        let spawner = scheduler.spawner();
+        let (order, recorded) = channel();
+        let (unblock, blocked) = channel::<()>();
+        let blocked = Mutex::new(blocked);
+        let speculative_order = order.clone();
+        spawner.spawn(
+            || Priority::Speculative,
+            move |_| {
+                blocked.lock().unwrap().recv().unwrap();
+                speculative_order.send("speculative 1").unwrap();
+            },
+        );
+        spawner.spawn(|| Priority::Speculative, record(&order, "speculative 2"));
+        spawner.spawn(|| Priority::Demanded, record(&order, "demanded"));
+        assert_eq!(recorded.recv().unwrap(), "demanded");
+        unblock.send(()).unwrap();
+        assert_eq!(
+            recorded.iter().take(2).collect::<Vec<_>>(),
+            vec!["speculative 1", "speculative 2"]
+        );
+    }
+}